@@ -0,0 +1,159 @@
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::entities::{Blink, Ship, Shot};
+use crate::entities::weapon::Weapon;
+use crate::graphics::{FakeTransform, Palette};
+use crate::graphics::sizes::Hitbox;
+use crate::logic::boss::BossPart;
+use crate::logic::damage::Dead;
+use crate::logic::hit::HitEvent;
+use crate::logic::upgrades::PIERCING;
+use crate::screens::Textures;
+use crate::util::{space, z_pos};
+
+/// Line from its ship to the first ship in the way, hitting every [space::BEAM_TICK] while active.
+/// The [Shot] of the beam carries the damage of a tick.
+#[derive(Component)]
+pub struct Beam {
+    /// Ship firing the beam, the boss for the beams of its parts
+    owner: Entity,
+    /// From the center of the owner
+    offset: Vec2,
+    direction: Vec2,
+    /// Seconds until the beam deals damage
+    telegraph: f32,
+    /// Seconds the beam stays active once telegraphed
+    duration: f32,
+    /// Seconds until the next tick
+    tick: f32,
+    /// Goes through every ship instead of stopping at the first one
+    piercing: bool,
+}
+
+pub fn spawn_beam(commands: &mut Commands, textures: &Textures, mut weapon: Weapon, owner: Entity, offset: Vec2, pos: Vec2, friendly: bool, upgrades: usize) {
+    let beam = Beam {
+        owner,
+        offset,
+        direction: weapon.speed.normalize_or_zero(),
+        telegraph: weapon.telegraph,
+        duration: space::BEAM_DURATION,
+        tick: 0.,
+        piercing: upgrades & PIERCING != 0,
+    };
+    // The beam follows its owner instead of flying
+    weapon.speed = Vec2::ZERO;
+    let mut entity = commands.spawn(SpriteBundle {
+        sprite: Sprite {
+            anchor: Anchor::BottomCenter,
+            color: if friendly { Palette::Greyscale.colors()[2] } else { Palette::Greyscale.colors()[1] },
+            ..default()
+        },
+        texture: textures.bar.clone(),
+        ..default()
+    });
+    entity
+        .insert(Shot::new(weapon, friendly))
+        .insert(FakeTransform::from_xyz_and_scale(pos.x, pos.y, z_pos::SHOTS, 1., 1.))
+    ;
+    if beam.telegraph > 0. { entity.insert(Blink(beam.telegraph)); }
+    entity.insert(beam);
+}
+
+/// Distance from [origin] along [direction] to the box of [size] centered on [center], if the ray hits it
+pub fn ray_hit(origin: Vec2, direction: Vec2, center: Vec2, size: Vec2) -> Option<f32> {
+    let (min, max) = (center - size / 2., center + size / 2.);
+    let (mut near, mut far) = (0f32, f32::INFINITY);
+    for axis in 0..2 {
+        if direction[axis].abs() < 0.0001 {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] { return None; }
+            continue;
+        }
+        let (a, b) = ((min[axis] - origin[axis]) / direction[axis], (max[axis] - origin[axis]) / direction[axis]);
+        near = near.max(a.min(b));
+        far = far.min(a.max(b));
+        if near > far { return None; }
+    }
+    Some(near)
+}
+
+/// Follows the owner of the beams, stretches them to what they hit and sends the hits of the ticks
+pub fn update_beams(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut beams: Query<(Entity, &mut Beam, &Shot, &mut FakeTransform, &mut Transform)>,
+    ships: Query<(Entity, &Ship, &Hitbox, &FakeTransform), (Without<Dead>, Without<Beam>)>,
+    parts: Query<(&BossPart, &Parent, &Hitbox, Entity)>,
+    mut event_writer: EventWriter<HitEvent>,
+) {
+    let dt = time.delta_seconds();
+    for (e, mut beam, shot, mut pos, mut transform) in beams.iter_mut() {
+        let Ok((_, _, _, owner_pos)) = ships.get(beam.owner) else {
+            commands.entity(e).despawn_recursive();
+            continue;
+        };
+        let origin = owner_pos.translation.xy() + beam.offset;
+        let width = shot.weapon.shot.hitbox().0.x * shot.weapon.size.ceil();
+
+        // Parts cover their boss
+        let mut hits = vec![];
+        let mut covered = vec![];
+        for (part, boss, hitbox, part_entity) in &parts {
+            let Ok((_, ship, _, ship_pos)) = ships.get(boss.get()) else { continue; };
+            if shot.friendly == ship.friendly { continue; }
+            let center = ship_pos.translation.xy() + part.offset;
+            let Some(distance) = ray_hit(origin, beam.direction, center, hitbox.0 + width) else { continue; };
+            covered.push(boss.get());
+            hits.push((distance, boss.get(), Some(part_entity)));
+        }
+        for (ship_entity, ship, hitbox, ship_pos) in &ships {
+            if shot.friendly == ship.friendly || covered.contains(&ship_entity) { continue; }
+            let Some(distance) = ray_hit(origin, beam.direction, ship_pos.translation.xy(), hitbox.0 + width) else { continue; };
+            hits.push((distance, ship_entity, None));
+        }
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if !beam.piercing { hits.truncate(1); }
+        let length = if beam.piercing { None } else { hits.first().map(|hit| hit.0) }.unwrap_or(space::BEAM_LENGTH);
+
+        // Thin while telegraphed
+        let telegraphed = beam.telegraph > 0.;
+        pos.translation = origin.extend(z_pos::SHOTS);
+        // The bar sprite is 2 pixels high
+        pos.scale = Some(vec2(if telegraphed { 1. } else { width }, length / 2.));
+        transform.rotation = Quat::from_rotation_z(beam.direction.y.atan2(beam.direction.x) - std::f32::consts::FRAC_PI_2);
+
+        if telegraphed {
+            beam.telegraph -= dt;
+            continue;
+        }
+        beam.duration -= dt;
+        if beam.duration <= 0. {
+            commands.entity(e).despawn_recursive();
+            continue;
+        }
+        beam.tick -= dt;
+        if beam.tick > 0. { continue; }
+        beam.tick += space::BEAM_TICK;
+        for (_, ship, part) in hits {
+            event_writer.send(HitEvent { shot: e, ship, part });
+        }
+    }
+}
+
+#[test]
+fn beams_hit_boxes_in_their_way() {
+    let up = Vec2::new(0., 1.);
+    assert_eq!(ray_hit(Vec2::ZERO, up, vec2(0., 20.), vec2(4., 4.)), Some(18.));
+    assert_eq!(ray_hit(Vec2::ZERO, up, vec2(2., 20.), vec2(4., 4.)), Some(18.));
+    assert_eq!(ray_hit(Vec2::ZERO, up, vec2(5., 20.), vec2(4., 4.)), None);
+    // Nothing behind the ship
+    assert_eq!(ray_hit(Vec2::ZERO, up, vec2(0., -20.), vec2(4., 4.)), None);
+    // Starting inside the box
+    assert_eq!(ray_hit(Vec2::ZERO, up, Vec2::ZERO, vec2(4., 4.)), Some(0.));
+
+    let diagonal = Vec2::new(1., 1.).normalize();
+    let distance = ray_hit(Vec2::ZERO, diagonal, vec2(10., 10.), vec2(2., 2.)).unwrap();
+    assert!((distance - 9. * 2f32.sqrt()).abs() < 0.001);
+    assert_eq!(ray_hit(Vec2::ZERO, diagonal, vec2(10., -10.), vec2(2., 2.)), None);
+}
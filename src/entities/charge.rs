@@ -0,0 +1,136 @@
+use bevy::math::{vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::entities::{beam, MainShip, MuteShots, Ship, Targeting, Weapon, Weapons};
+use crate::entities::shot::spawn_shot;
+use crate::graphics::FakeTransform;
+use crate::logic::upgrades::{PIERCING, ShotUpgrades};
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::Textures;
+use crate::util::{Angle, charge};
+
+fn one() -> usize { 1 }
+
+/// Shots of a playable hull released after holding fire, see `fleet.ships.ron`
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct ChargedShot {
+    pub weapon: Weapons,
+    /// Shots fired at once, fanned over [ChargedShot::spread] degrees
+    #[serde(default = "one")]
+    pub count: usize,
+    #[serde(default)]
+    pub spread: f32,
+    /// Multiplier of the damage at full charge
+    pub damage: f32,
+    /// Multiplier of the size at full charge
+    pub size: f32,
+}
+
+impl ChargedShot {
+    pub fn check(&self) -> Result<(), String> {
+        if self.count == 0 { return Err("charged shots need at least one shot".to_string()); }
+        if self.damage < 1. || self.size < 1. { return Err("charged shots can't be weaker than the usual ones".to_string()); }
+        Ok(())
+    }
+
+    /// Weapons fired after charging up to [level], between 0 and 1
+    pub fn weapons(&self, level: f32, ship: &Ship) -> Vec<Weapon> {
+        let damage = 1. + (self.damage - 1.) * level;
+        let size = 1. + (self.size - 1.) * level;
+        (0..self.count)
+            .map(|i| {
+                let angle = if self.count == 1 { 90. } else { 90. + self.spread / 2. - self.spread * i as f32 / (self.count - 1) as f32 };
+                let mut weapon = Weapon::new(self.weapon.shot_type(), ship, vec2(0., 6.), Targeting::Fixed(Angle(angle)));
+                weapon.attack *= damage;
+                weapon.size = size;
+                weapon.telegraph = self.weapon.telegraph();
+                weapon
+            })
+            .collect()
+    }
+}
+
+/// Charge of the main ship, filling while fire is held
+#[derive(Component)]
+pub struct Charge {
+    shot: ChargedShot,
+    /// From 0 to 1
+    pub level: f32,
+    /// Multiplier of the charge speed
+    speed: f32,
+    held: bool,
+}
+
+impl Charge {
+    /// Damage and shot frequency upgrades both speed up the charge
+    pub fn new(shot: ChargedShot, damage_multiplier: f32, shot_frequency_multiplier: f32) -> Self {
+        Charge { shot, level: 0., speed: (damage_multiplier + shot_frequency_multiplier) / 2., held: false }
+    }
+
+    fn fill(&mut self, dt: f32) {
+        self.level = (self.level + dt * self.speed / charge::CHARGE_TIME).min(1.);
+    }
+}
+
+/// Auto-fire is paused while charging
+#[derive(Component)]
+pub struct Charging;
+
+pub fn charge(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    textures: Res<Textures>,
+    mut ships: Query<(Entity, &Ship, &FakeTransform, &mut Charge, Option<&ShotUpgrades>), (With<MainShip>, Without<MuteShots>)>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    for (e, ship, pos, mut charge, upgrades) in ships.iter_mut() {
+        if keys.pressed(KeyCode::Space) {
+            if !charge.held {
+                charge.held = true;
+                commands.entity(e).insert(Charging);
+            }
+            charge.fill(time.delta_seconds());
+            continue;
+        }
+        if !charge.held { continue; }
+        charge.held = false;
+        commands.entity(e).remove::<Charging>();
+        let level = std::mem::take(&mut charge.level);
+        if level < charge::MIN_CHARGE { continue; }
+
+        let mut upgrades = upgrades.map_or(0, |u| u.0);
+        if level >= charge::PIERCING_CHARGE { upgrades |= PIERCING; }
+        for weapon in charge.shot.weapons(level, ship) {
+            let from = pos.translation.xy() + weapon.offset;
+            if weapon.is_beam() { beam::spawn_beam(&mut commands, &textures, weapon, e, weapon.offset, from, true, upgrades); }
+            else { spawn_shot(&mut commands, &textures, weapon, from, true, upgrades); }
+        }
+        sfx.send(PlaySFXEvent(if level >= charge::PIERCING_CHARGE { SFX::Missile } else { SFX::ShipFire }));
+    }
+}
+
+#[test]
+fn charged_shots_scale_with_the_charge() {
+    let ship = Ship { shot_speed: 100., shot_frequency: 1., damage_factor: 1., friendly: true, ..default() };
+    let charged = ChargedShot { weapon: Weapons::Standard, count: 3, spread: 60., damage: 5., size: 3. };
+    let weak = charged.weapons(0., &ship);
+    let full = charged.weapons(1., &ship);
+    assert_eq!(full.len(), 3);
+    assert_eq!(full[0].attack, weak[0].attack * 5.);
+    assert_eq!(full[0].size, 3.);
+    assert_eq!(weak[0].size, 1.);
+    // Fanned around straight ahead
+    assert!(full[1].speed.abs_diff_eq(vec2(0., 100.), 0.01));
+    assert!((full[0].speed.x + full[2].speed.x).abs() < 0.01);
+    assert!(ChargedShot { count: 0, ..charged }.check().is_err());
+
+    let mut charge = Charge::new(charged, 1., 1.);
+    let mut upgraded = Charge::new(charged, 1.5, 1.5);
+    charge.fill(charge::CHARGE_TIME / 2.);
+    upgraded.fill(charge::CHARGE_TIME / 2.);
+    assert!(upgraded.level > charge.level);
+    upgraded.fill(charge::CHARGE_TIME);
+    assert_eq!(upgraded.level, 1.);
+}
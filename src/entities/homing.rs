@@ -0,0 +1,92 @@
+use bevy::math::{Vec2, Vec3Swizzles};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::entities::{Ship, Shot};
+use crate::graphics::FakeTransform;
+use crate::logic::damage::Dead;
+
+/// Steering of a shot toward the closest opposing ship
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct Homing {
+    /// In degrees per second
+    pub turn_rate: f32,
+    /// Distance from which targets are acquired
+    pub range: f32,
+    /// Milliseconds flying straight before steering
+    #[serde(default)]
+    pub lock_on: usize,
+}
+
+impl Homing {
+    pub fn check(&self) -> Result<(), String> {
+        if self.turn_rate <= 0. { return Err("homing shots need a positive turn rate".to_string()); }
+        if self.range <= 0. { return Err("homing shots need a positive range".to_string()); }
+        Ok(())
+    }
+}
+
+#[derive(Component)]
+pub struct HomingShot {
+    homing: Homing,
+    /// Seconds since the shot was fired
+    age: f32,
+    target: Option<Entity>,
+}
+
+impl HomingShot {
+    pub fn new(homing: Homing) -> Self { HomingShot { homing, age: 0., target: None } }
+}
+
+/// [velocity] turned toward [direction] by at most [max_angle] degrees
+fn steer(velocity: Vec2, direction: Vec2, max_angle: f32) -> Vec2 {
+    if velocity == Vec2::ZERO || direction == Vec2::ZERO { return velocity; }
+    let angle = velocity.angle_between(direction).to_degrees().clamp(-max_angle, max_angle);
+    Vec2::from_angle(angle.to_radians()).rotate(velocity)
+}
+
+/// Homing shots pick a new target when theirs dies or gets out of range.
+/// Shields draw the enemy shots aimed at the player.
+pub fn home_shots(
+    time: Res<Time>,
+    mut shots: Query<(&mut Shot, &mut HomingShot, &FakeTransform, &mut Transform)>,
+    ships: Query<(Entity, &Ship, &FakeTransform), Without<Dead>>,
+) {
+    for (mut shot, mut homing, pos, mut transform) in shots.iter_mut() {
+        homing.age += time.delta_seconds();
+        if homing.age < homing.homing.lock_on as f32 / 1000. { continue; }
+
+        let pos = pos.translation.xy();
+        let range = homing.homing.range;
+        let in_range = |target: &FakeTransform| target.translation.xy().distance(pos) <= range;
+        if !homing.target.is_some_and(|target| ships.get(target).is_ok_and(|(_, _, target)| in_range(target))) {
+            homing.target = ships.iter()
+                .filter(|(_, ship, target)| ship.friendly != shot.friendly && ship.health > 0.001 && in_range(target))
+                .min_by(|(_, a, a_pos), (_, b, b_pos)| (!a.model.is_shield()).cmp(&!b.model.is_shield())
+                    .then(a_pos.translation.xy().distance(pos).total_cmp(&b_pos.translation.xy().distance(pos))))
+                .map(|(e, _, _)| e);
+        }
+        let Some((_, _, target)) = homing.target.and_then(|target| ships.get(target).ok()) else { continue; };
+
+        let max_angle = homing.homing.turn_rate * time.delta_seconds();
+        shot.weapon.speed = steer(shot.weapon.speed, target.translation.xy() - pos, max_angle);
+        // Sprites face up, enemy ones are flipped to face down
+        let facing = if shot.friendly { 90. } else { 270. };
+        let angle = shot.weapon.speed.y.atan2(shot.weapon.speed.x).to_degrees() - facing;
+        transform.rotation = Quat::from_rotation_z(angle.to_radians());
+    }
+}
+
+#[test]
+fn homing_shots_turn_gradually() {
+    let velocity = Vec2::new(0., 100.);
+    let turned = steer(velocity, Vec2::new(50., 0.), 30.);
+    assert!((velocity.angle_between(turned).to_degrees() + 30.).abs() < 0.01);
+    assert!((turned.length() - 100.).abs() < 0.01);
+    // No overshoot when the target is within reach
+    let turned = steer(velocity, Vec2::new(-1., 10.), 30.);
+    assert!(turned.normalize().abs_diff_eq(Vec2::new(-1., 10.).normalize(), 0.001));
+
+    let homing = Homing { turn_rate: 0., range: 80., lock_on: 0 };
+    assert!(homing.check().is_err());
+}
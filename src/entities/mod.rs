@@ -1,15 +1,26 @@
 use bevy::app::App;
 use bevy::prelude::*;
 
+pub use charge::Charge;
+pub use charge::ChargedShot;
+pub use homing::Homing;
+pub use homing::HomingShot;
+pub use pattern::BulletPatterns;
+pub use pattern::ShipPattern;
 pub use ship::Blink;
 pub use ship::MainShip;
 pub use ship::Ship;
+pub use ship::ShipRegistry;
+pub use ship::WeaponModule;
+#[cfg(test)]
+pub use ship::registry;
 pub use ship::Ships;
 pub use shot::MuteShots;
 pub use shot::MuteShotsFor;
 pub use shot::Shot;
 pub use shot::Shots;
 pub use weapon::ShipWeapons;
+pub use weapon::Targeting;
 pub use weapon::Weapon;
 pub use weapon::Weapons;
 
@@ -19,6 +30,10 @@ use crate::entities::shot::ShotsPlugin;
 mod ship;
 mod weapon;
 mod shot;
+mod pattern;
+mod homing;
+mod beam;
+mod charge;
 
 pub struct EntitiesPlugin;
 
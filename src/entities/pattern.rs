@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use bevy::math::{Vec2, vec2};
+use bevy::prelude::Component;
+use bevy::reflect::{TypePath, TypeUuid};
+use serde::Deserialize;
+
+use crate::entities::{Homing, ShipRegistry, Ships, Shots};
+use crate::util::Angle;
+
+/// Direction of a shot, in degrees
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum Direction {
+    /// Relative to the facing of the ship, down for enemies
+    Ahead(f32),
+    Absolute(f32),
+    /// Relative to the direction of the player, or ahead if there is none
+    Aimed(f32),
+    /// Relative to the previous shot of the pattern, for spirals
+    Sequence(f32),
+}
+
+impl Default for Direction {
+    fn default() -> Self { Direction::Ahead(0.) }
+}
+
+fn one() -> f32 { 1. }
+
+fn bullet() -> Shots { Shots::Bullet }
+
+/// A single shot
+#[derive(Clone, Debug, Deserialize)]
+pub struct Fire {
+    #[serde(default = "bullet")]
+    pub shot: Shots,
+    #[serde(default)]
+    pub direction: Direction,
+    /// Ratio of the shot speed of the ship
+    #[serde(default = "one")]
+    pub speed: f32,
+    /// Offset from the ship
+    #[serde(default)]
+    pub offset: (f32, f32),
+    /// The shot is replaced by other shots after a while
+    #[serde(default)]
+    pub split: Option<Box<Split>>,
+    #[serde(default)]
+    pub homing: Option<Homing>,
+}
+
+/// Shots fired from the position of a shot after [Split::after] ms, [Direction::Ahead] and
+/// [Direction::Sequence] are relative to the direction of the shot
+#[derive(Clone, Debug, Deserialize)]
+pub struct Split {
+    pub after: usize,
+    pub then: Action,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum Action {
+    Fire(Fire),
+    /// n shots evenly spread around, the first one in the direction of the [Fire]
+    Ring(usize, Fire),
+    /// n shots [f32] degrees apart, centered on the direction of the [Fire]
+    Fan(usize, f32, Fire),
+    /// Milliseconds
+    Wait(usize),
+    /// Run the actions n times
+    Repeat(usize, Vec<Action>),
+}
+
+impl Action {
+    /// Milliseconds waited by the action
+    fn duration(&self) -> usize {
+        match self {
+            Action::Wait(ms) => *ms,
+            Action::Repeat(n, actions) => n * actions.iter().map(Action::duration).sum::<usize>(),
+            _ => 0,
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let fire = match self {
+            Action::Fire(fire) => fire,
+            Action::Ring(0, _) | Action::Fan(0, _, _) => return Err("a ring or a fan needs at least 1 shot".to_string()),
+            Action::Ring(_, fire) | Action::Fan(_, _, fire) => fire,
+            Action::Wait(_) => return Ok(()),
+            Action::Repeat(0, _) => return Err("a repeat needs at least 1 repetition".to_string()),
+            Action::Repeat(_, actions) => return actions.iter().try_for_each(Action::check),
+        };
+        if fire.speed <= 0. { return Err("shot speeds must be positive".to_string()); }
+        // Beams aren't projectiles, they are spawned by `beam::spawn_beam`
+        if matches!(fire.shot, Shots::Beam) { return Err("patterns can't fire beams".to_string()); }
+        if let Some(homing) = fire.homing { homing.check()?; }
+        match &fire.split {
+            Some(split) if split.then.duration() > 0 => Err("a split can't wait".to_string()),
+            Some(split) => split.then.check(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where the shots of a pattern come from
+pub struct Emitter {
+    pub pos: Vec2,
+    /// Position of the player, for [Direction::Aimed]
+    pub target: Option<Vec2>,
+    /// Direction of [Direction::Ahead], in degrees
+    pub facing: f32,
+    pub shot_speed: f32,
+}
+
+/// Shot fired by a pattern
+#[derive(Debug)]
+pub struct PatternShot {
+    pub shot: Shots,
+    pub pos: Vec2,
+    pub speed: Vec2,
+    /// In degrees
+    pub direction: f32,
+    pub split: Option<Box<Split>>,
+    pub homing: Option<Homing>,
+}
+
+impl Fire {
+    fn angle(&self, previous: &mut f32, pos: Vec2, emitter: &Emitter) -> f32 {
+        let angle = match self.direction {
+            Direction::Ahead(angle) => emitter.facing + angle,
+            Direction::Absolute(angle) => angle,
+            Direction::Aimed(angle) => angle + emitter.target
+                .map_or(emitter.facing, |target| (target - pos).y.atan2((target - pos).x).to_degrees()),
+            Direction::Sequence(angle) => *previous + angle,
+        };
+        *previous = angle;
+        angle
+    }
+
+    fn shot(&self, angle: f32, pos: Vec2, emitter: &Emitter) -> PatternShot {
+        PatternShot {
+            shot: self.shot,
+            pos,
+            speed: Angle(angle).rotate(emitter.shot_speed * self.speed),
+            direction: angle,
+            split: self.split.clone(),
+            homing: self.homing,
+        }
+    }
+}
+
+/// Fire the shots of [action] right away, waits are ignored
+pub fn fire(action: &Action, previous: &mut f32, emitter: &Emitter, shots: &mut Vec<PatternShot>) {
+    match action {
+        Action::Fire(fire) => {
+            let pos = emitter.pos + vec2(fire.offset.0, fire.offset.1);
+            let angle = fire.angle(previous, pos, emitter);
+            shots.push(fire.shot(angle, pos, emitter));
+        }
+        Action::Ring(n, fire) => {
+            let pos = emitter.pos + vec2(fire.offset.0, fire.offset.1);
+            let angle = fire.angle(previous, pos, emitter);
+            for i in 0..*n {
+                shots.push(fire.shot(angle + 360. * i as f32 / *n as f32, pos, emitter));
+            }
+        }
+        Action::Fan(n, spread, fire) => {
+            let pos = emitter.pos + vec2(fire.offset.0, fire.offset.1);
+            let angle = fire.angle(previous, pos, emitter);
+            for i in 0..*n {
+                shots.push(fire.shot(angle + (i as f32 - (*n - 1) as f32 / 2.) * spread, pos, emitter));
+            }
+        }
+        Action::Wait(_) => {}
+        Action::Repeat(n, actions) => {
+            for _ in 0..*n {
+                for action in actions.iter() { fire(action, previous, emitter, shots); }
+            }
+        }
+    }
+}
+
+/// Runs a pattern in a loop, in place of the weapons of the ship
+#[derive(Component)]
+pub struct ShipPattern {
+    actions: Vec<Action>,
+    /// Index of the next action of the pattern and of each nested [Action::Repeat] being run,
+    /// with the repetitions left
+    stack: Vec<(usize, usize)>,
+    /// Seconds until the next action
+    wait: f32,
+    /// Direction of the previous shot, for [Direction::Sequence]
+    previous: f32,
+}
+
+impl ShipPattern {
+    pub fn new(actions: Vec<Action>) -> Self {
+        ShipPattern { actions, stack: vec![(0, 0)], wait: 0., previous: 0. }
+    }
+
+    /// Run the actions due in the next [dt] seconds, the shots fired are added to [shots]
+    pub fn step(&mut self, dt: f32, emitter: &Emitter, shots: &mut Vec<PatternShot>) {
+        self.wait -= dt;
+        while self.wait <= 0. {
+            let mut actions = &self.actions;
+            for &(i, _) in self.stack[..self.stack.len() - 1].iter() {
+                if let Action::Repeat(_, repeated) = &actions[i] { actions = repeated; }
+            }
+            let (i, repetitions) = *self.stack.last().unwrap();
+            match actions.get(i) {
+                // The pattern loops, [BulletPatterns] makes sure that it waits
+                None if self.stack.len() == 1 => { self.stack[0].0 = 0; continue; }
+                None => {
+                    self.stack.pop();
+                    if repetitions > 0 { self.stack.push((0, repetitions - 1)); }
+                    else { self.stack.last_mut().unwrap().0 += 1; }
+                    continue;
+                }
+                Some(Action::Repeat(n, _)) => { self.stack.push((0, n - 1)); continue; }
+                Some(Action::Wait(ms)) => self.wait += *ms as f32 / 1000.,
+                Some(action) => fire(action, &mut self.previous, emitter, shots),
+            }
+            self.stack.last_mut().unwrap().0 += 1;
+        }
+    }
+}
+
+/// Named bullet patterns and the ships using them, loaded from `assets/data/enemies.patterns.ron`
+#[derive(Clone, Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "5c2a9e71-0d48-4f3b-a6e2-91b7d4c8f0a3"]
+#[serde(try_from = "BulletPatternsFile")]
+pub struct BulletPatterns {
+    patterns: HashMap<String, Vec<Action>>,
+    /// Ships firing a pattern instead of weapons, see [BulletPatterns::check_ships]
+    ships: HashMap<Ships, String>,
+}
+
+/// [BulletPatterns] as written in the asset, before validation
+#[derive(Deserialize)]
+struct BulletPatternsFile {
+    patterns: HashMap<String, Vec<Action>>,
+    ships: HashMap<Ships, String>,
+}
+
+impl TryFrom<BulletPatternsFile> for BulletPatterns {
+    type Error = String;
+
+    fn try_from(file: BulletPatternsFile) -> Result<Self, Self::Error> {
+        for (name, actions) in file.patterns.iter() {
+            actions.iter().try_for_each(Action::check).map_err(|e| format!("patterns: {}: {}", name, e))?;
+            if actions.iter().map(Action::duration).sum::<usize>() == 0 {
+                return Err(format!("patterns: {}: never waits", name));
+            }
+        }
+        for (ship, pattern) in file.ships.iter() {
+            // Their weapons are checked against the ship registry
+            if !Ships::required().any(|model| model == *ship) {
+                return Err(format!("ships: {:?} doesn't exist", ship));
+            }
+            if !file.patterns.contains_key(pattern) {
+                return Err(format!("ships: {:?}: unknown pattern {}", ship, pattern));
+            }
+        }
+        Ok(BulletPatterns { patterns: file.patterns, ships: file.ships })
+    }
+}
+
+impl BulletPatterns {
+    /// Pattern fired by [model], if any
+    pub fn of(&self, model: Ships) -> Option<ShipPattern> {
+        self.ships.get(&model).map(|name| ShipPattern::new(self.patterns[name].clone()))
+    }
+
+    /// The weapons of a ship firing a pattern would never fire, they are checked once both files are loaded
+    pub fn check_ships(&self, ships: &ShipRegistry) -> Result<(), String> {
+        match self.ships.keys().find(|&&model| !ships.get(model).weapons.is_empty()) {
+            Some(model) => Err(format!("ships: {:?} fires a pattern but has weapons in the ship registry", model)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+fn emitter() -> Emitter {
+    Emitter { pos: vec2(80., 100.), target: Some(vec2(80., 20.)), facing: 270., shot_speed: 100. }
+}
+
+#[cfg(test)]
+fn run(actions: Vec<Action>, seconds: f32) -> Vec<(f32, PatternShot)> {
+    let mut pattern = ShipPattern::new(actions);
+    let mut shots = vec![];
+    let mut t = 0.;
+    while t < seconds {
+        let mut fired = vec![];
+        pattern.step(1. / 60., &emitter(), &mut fired);
+        shots.extend(fired.into_iter().map(|shot| (t, shot)));
+        t += 1. / 60.;
+    }
+    shots
+}
+
+#[test]
+fn patterns_fire_in_sequence() {
+    let patterns: BulletPatterns = ron::from_str(r#"(
+        patterns: {
+            "burst": [Wait(500), Repeat(3, [Fire((direction: Aimed(0.))), Wait(100)])],
+            "spiral": [Fire((direction: Sequence(90.), speed: 0.5)), Wait(250)],
+            "ring": [Ring(4, (offset: (0., -8.))), Wait(1000)],
+        },
+        ships: { Invader(0): "burst" },
+    )"#).unwrap();
+
+    // 3 aimed shots 100 ms apart every 800 ms
+    let burst = run(patterns.patterns["burst"].clone(), 2.);
+    assert_eq!(burst.len(), 6);
+    assert!((burst[1].0 - burst[0].0 - 0.1).abs() < 0.02);
+    assert!((burst[3].0 - burst[0].0 - 0.8).abs() < 0.02);
+    assert!(burst.iter().all(|(_, shot)| shot.speed.abs_diff_eq(vec2(0., -100.), 0.01)));
+    assert!(patterns.of(Ships::Invader(0)).is_some());
+    assert!(patterns.of(Ships::Invader(1)).is_none());
+
+    let spiral = run(patterns.patterns["spiral"].clone(), 0.9);
+    let speeds: Vec<Vec2> = spiral.iter().map(|(_, shot)| shot.speed).collect();
+    assert_eq!(speeds.len(), 4);
+    for (speed, expected) in speeds.iter().zip([vec2(0., 50.), vec2(-50., 0.), vec2(0., -50.), vec2(50., 0.)]) {
+        assert!(speed.abs_diff_eq(expected, 0.01));
+    }
+
+    let ring = run(patterns.patterns["ring"].clone(), 0.5);
+    assert_eq!(ring.len(), 4);
+    assert!(ring.iter().all(|(_, shot)| shot.pos == vec2(80., 92.)));
+}
+
+#[test]
+fn splits_fire_from_the_shot() {
+    let action: Action = ron::from_str("Fire((split: Some((after: 500, then: Fan(3, 30., (direction: Sequence(0.)))))))").unwrap();
+    let mut shots = vec![];
+    fire(&action, &mut 0., &emitter(), &mut shots);
+    let split = shots[0].split.as_ref().unwrap();
+    assert_eq!(split.after, 500);
+
+    let mut previous = shots[0].direction;
+    let mut fan = vec![];
+    fire(&split.then, &mut previous, &Emitter { pos: vec2(80., 50.), ..emitter() }, &mut fan);
+    let directions: Vec<f32> = fan.iter().map(|shot| shot.direction).collect();
+    assert_eq!(directions, vec![240., 270., 300.]);
+    assert!(fan.iter().all(|shot| shot.pos == vec2(80., 50.)));
+}
+
+#[test]
+fn invalid_patterns_are_rejected() {
+    crate::util::assert_rejected::<BulletPatterns>(r#"(patterns: { "a": [Wait(10)] }, ships: {})"#, &[
+        ("[Wait(10)]", "[Fire(())]", "never waits"),
+        ("ships: {}", r#"ships: { Boss(0): "b" }"#, "unknown pattern b"),
+        ("ships: {}", r#"ships: { Invader(20): "a" }"#, "Invader(20) doesn't exist"),
+        ("[Wait(10)]", "[Ring(0, ()), Wait(10)]", "at least 1 shot"),
+        ("[Wait(10)]", "[Fan(2, 10., (shot: Beam)), Wait(10)]", "can't fire beams"),
+        ("[Wait(10)]", "[Fire((split: Some((after: 1, then: Wait(5))))), Wait(10)]", "a split can't wait"),
+    ]);
+    let patterns = ron::from_str::<BulletPatterns>(include_str!("../../assets/data/enemies.patterns.ron")).unwrap();
+    assert!(patterns.check_ships(&crate::entities::registry()).is_ok());
+    let armed = ron::from_str::<BulletPatterns>(r#"(patterns: { "a": [Wait(10)] }, ships: { Invader(7): "a" })"#).unwrap();
+    assert!(armed.check_ships(&crate::entities::registry()).is_err_and(|e| e.contains("Invader(7)")));
+}
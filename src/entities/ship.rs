@@ -1,18 +1,17 @@
 use bevy::app::App;
-use bevy::math::vec2;
 use bevy::prelude::*;
-use rand::{Rng, thread_rng};
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::HashMap;
+use serde::Deserialize;
 
-use crate::entities::Weapons;
-use crate::graphics::sizes::Hitbox;
+use crate::entities::{ChargedShot, Targeting, Weapons};
 use crate::logic::damage::DamageEvent;
-use crate::logic::route::Route;
-use crate::util::{Angle, base_stats};
+use crate::util::base_stats;
 use crate::util::space::{BLINK_DURATION, BLINK_DURATION_ELITE, BLINK_DURATION_ENEMY, BLINK_INTERVAL};
 
 pub struct ShipPlugin;
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize)]
 pub enum Ships {
     Player(u8),
     Invader(u8),
@@ -39,95 +38,12 @@ impl Ships {
         }
     }
 
-    pub fn random_enemy(level: usize) -> Self {
-        let mut rng = thread_rng();
-        let possible = [
-            vec![0, 1, 2, 3],
-            vec![0, 1, 2, 3, 4, 4, 5, 5, 6, 6],
-            vec![0, 1, 2, 3, 4, 4, 5, 5, 6, 6, 7, 7, 7, 8, 8, 8],
-        ];
-        let act = level / Route::act_len();
-        let possible = possible[act].to_owned();
-        return Ships::Invader(possible[rng.gen_range(0..possible.len())]);
-    }
-
-    pub fn hitbox(&self) -> Hitbox {
-        match self {
-            Ships::Player(_) => Hitbox(vec2(6., 4.)),
-            Ships::Invader(_) => Hitbox(vec2(12., 6.)),
-            Ships::Elite(_) => Hitbox(vec2(8., 4.)),
-            Ships::Boss(_) => Hitbox(vec2(48., 24.)),
-        }
-    }
-
-    pub fn weapons(&self) -> Vec<(Weapons, Vec2, Angle)> {
-        match self {
-            Ships::Player(0) => vec![
-                (Weapons::Standard, vec2(-4., 6.), Angle(90.)),
-                (Weapons::Standard, vec2(4., 6.), Angle(90.)),
-            ],
-            Ships::Player(1) => vec![
-                (Weapons::Wave, vec2(-5., 6.), Angle(90.)),
-                (Weapons::Wave, vec2(5., 6.), Angle(90.)),
-            ],
-            Ships::Player(2) => vec![
-                (Weapons::Standard, vec2(-4., 6.), Angle(115.)),
-                (Weapons::Standard, vec2(0., 6.), Angle(90.)),
-                (Weapons::Standard, vec2(4., 6.), Angle(65.)),
-            ],
-            Ships::Player(_) => vec![
-                (Weapons::Energy, vec2(0., 6.), Angle(90.)),
-            ],
-            Ships::Elite(n) => Ships::Player(*n)
-                .weapons()
-                .iter()
-                .map(|&(w, pos, Angle(a))| (w, vec2(pos.x, -pos.y), Angle(360. - a)))
-                .collect()
-            ,
-            Ships::Invader(0) | Ships::Invader(2) => vec![
-                (Weapons::Standard, vec2(0., -3.), Angle(270.)),
-            ],
-            Ships::Invader(1) => vec![
-                (Weapons::Standard, vec2(-2., -3.), Angle(270.)),
-                (Weapons::Standard, vec2(2., -3.), Angle(270.)),
-            ],
-            Ships::Invader(3) => vec![
-                (Weapons::Standard, vec2(-5., -3.), Angle(225.)),
-                (Weapons::Standard, vec2(5., -3.), Angle(315.)),
-            ],
-            Ships::Invader(4) | Ships::Invader(7) => vec![
-                (Weapons::Dual, vec2(0., -3.), Angle(270.)),
-            ],
-            Ships::Invader(5) => vec![
-                (Weapons::Dual, vec2(0., -3.), Angle(270.)),
-                (Weapons::Standard, vec2(-5., -3.), Angle(225.)),
-                (Weapons::Standard, vec2(5., -3.), Angle(315.)),
-            ],
-            Ships::Invader(6) => vec![
-                (Weapons::Standard, vec2(0., -3.), Angle(270.)),
-                (Weapons::Standard, vec2(-5., -3.), Angle(225.)),
-                (Weapons::Standard, vec2(5., -3.), Angle(315.)),
-            ],
-            Ships::Invader(8) => vec![
-                (Weapons::Standard, vec2(-5., -3.), Angle(270.)),
-                (Weapons::Standard, vec2(5., -3.), Angle(270.)),
-            ],
-            Ships::Boss(0) => vec![
-
-            ],
-            Ships::Boss(1) => vec![
-                (Weapons::Standard, vec2(-10., -24.), Angle(270.)),
-                (Weapons::Standard, vec2(10., -24.), Angle(270.)),
-            ],
-            Ships::Boss(2) => vec![
-                (Weapons::Standard, vec2(-16., -24.), Angle(270. - 45. - 22.5)),
-                (Weapons::Standard, vec2(-8., -24.), Angle(270. - 45.)),
-                (Weapons::Standard, vec2(0., -24.), Angle(270.)),
-                (Weapons::Standard, vec2(8., -24.), Angle(270. + 45.)),
-                (Weapons::Standard, vec2(16., -24.), Angle(270. + 45. + 22.5)),
-            ],
-            _ => vec![],
-        }
+    /// Models the code and the wave templates spawn by index, they must be in the [ShipRegistry]
+    pub(crate) fn required() -> impl Iterator<Item=Ships> {
+        (0..PLAYABLE_SHIPS as u8).map(Ships::Player)
+            .chain((0..9).map(Ships::Invader))
+            .chain((0..4).map(Ships::Elite))
+            .chain((0..3).map(Ships::Boss))
     }
 }
 
@@ -141,142 +57,185 @@ pub struct Ship {
     pub shot_frequency: f32,
     pub health: f32,
     pub max_health: f32,
+    /// Pixel scale of the sprite
+    pub scale: f32,
 }
 
 impl Ship {
-    fn new(model: Ships, friendly: bool) -> Self {
-        Self {
-            model, friendly,
-            // Base stats
-            health: base_stats::HEALTH,
-            max_health: base_stats::HEALTH,
-            speed: base_stats::SPEED,
-            damage_factor: base_stats::DAMAGE_FACTOR,
-            shot_speed: base_stats::SHOT_SPEED,
-            shot_frequency: base_stats::SHOT_FREQUENCY,
-        }
-    }
-
     pub(crate) fn shield() -> Self {
         Self {
             friendly: true,
             health: 9999.,
             max_health: 9999.,
+            scale: 1.,
             ..default()
         }
     }
+}
+
+/// Ships taking more than 18 sprites would need a bigger atlas, see [crate::screens::Textures]
+const SHIP_SPRITES: usize = 18;
 
-    fn with_speed(mut self, speed: f32) -> Self { self.speed = speed; self }
-    fn with_damage_factor(mut self, damage_factor: f32) -> Self { self.damage_factor = damage_factor; self }
-    fn with_shot_speed(mut self, shot_speed: f32) -> Self { self.shot_speed = shot_speed; self }
-    fn with_shot_frequency(mut self, shot_frequency: f32) -> Self { self.shot_frequency = shot_frequency; self }
-    fn with_health(mut self, health: f32) -> Self { self.health = health; self.max_health = health; self }
-
-    pub fn from(model: Ships) -> Self {
-        match model {
-            Ships::Player(0) => Ship::new(model, true)
-                .with_health(base_stats::HEALTH * 1.5)
-            ,
-            Ships::Elite(0) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 2.25)
-            ,
-            Ships::Player(1) => Ship::new(model, true)
-                .with_health(base_stats::HEALTH * 2.5)
-            ,
-            Ships::Elite(1) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 3.0)
-            ,
-            Ships::Player(2) => Ship::new(model, true)
-                .with_health(base_stats::HEALTH * 1.5)
-                .with_speed(base_stats::SPEED * 1.5)
-                .with_damage_factor(base_stats::DAMAGE_FACTOR * 1.2)
-            ,
-            Ships::Elite(2) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 2.25)
-            ,
-            Ships::Player(_) => Ship::new(model, true)
-                .with_health(base_stats::HEALTH * 0.75)
-            ,
-            Ships::Elite(_) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 1.50)
-            ,
-            Ships::Invader(0) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH / 2.)
-                .with_speed(base_stats::SPEED / 2.)
-            ,
-            Ships::Invader(1) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH / 1.5)
-                .with_speed(base_stats::SPEED / 3.)
-            ,
-            Ships::Invader(2) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH / 2.)
-                .with_speed(base_stats::SPEED / 1.5)
-                .with_shot_frequency(base_stats::SHOT_FREQUENCY * 1.5)
-            ,
-            Ships::Invader(3) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH / 1.5)
-                .with_speed(base_stats::SPEED / 2.)
-            ,
-            Ships::Invader(4) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH)
-                .with_speed(base_stats::SPEED / 1.5)
-            ,
-            Ships::Invader(5) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH / 2.0)
-                .with_speed(base_stats::SPEED / 1.25)
-                .with_damage_factor(base_stats::DAMAGE_FACTOR * 1.5)
-            ,
-            Ships::Invader(6) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 2.)
-                .with_speed(base_stats::SPEED / 2.)
-                .with_shot_frequency(base_stats::SHOT_FREQUENCY / 1.5)
-                .with_damage_factor(base_stats::DAMAGE_FACTOR * 1.25)
-            ,
-            Ships::Invader(7) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 1.5)
-                .with_speed(base_stats::SPEED / 1.25)
-                .with_damage_factor(base_stats::DAMAGE_FACTOR * 1.25)
-            ,
-            Ships::Invader(8) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 1.5)
-                .with_speed(base_stats::SPEED / 1.5)
-                .with_shot_frequency(base_stats::SHOT_FREQUENCY * 1.5)
-                .with_damage_factor(base_stats::DAMAGE_FACTOR * 1.25)
-            ,
-            Ships::Boss(0) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 14.)
-                .with_speed(base_stats::SPEED / 1.5)
-            ,
-            Ships::Boss(1) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 12.)
-                .with_shot_frequency(base_stats::SHOT_FREQUENCY / 1.25)
-                .with_speed(base_stats::SPEED / 1.25)
-            ,
-            Ships::Boss(2) => Ship::new(model, false)
-                .with_health(base_stats::HEALTH * 14.)
-                .with_speed(base_stats::SPEED / 1.1)
-                .with_shot_frequency(base_stats::SHOT_FREQUENCY * 1.15)
-                .with_damage_factor(base_stats::DAMAGE_FACTOR * 1.35)
-            ,
-            _ => Ship::new(model, false),
+/// Hulls offered in the hangar, see [crate::screens::PlayableShips]
+const PLAYABLE_SHIPS: usize = 4;
+
+/// Divisor of a base stat, for ratios like 1/3 that a multiplier can't write exactly
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(from = "(f32,)")]
+pub struct Div(f32);
+
+/// RON gives untagged enums the content of `Div(x)`, not its name
+impl From<(f32,)> for Div {
+    fn from((x,): (f32,)) -> Self { Div(x) }
+}
+
+/// Multiplier of a base stat, written `1.5` or `Div(3.)`
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Stat {
+    Times(f32),
+    Div(Div),
+}
+
+impl Stat {
+    fn of(&self, base: f32) -> f32 {
+        match *self {
+            Stat::Times(x) => base * x,
+            Stat::Div(Div(x)) => base / x,
         }
     }
 
-    pub fn sprite_index(&self) -> usize {
-        match self.model {
-            Ships::Player(n) => n as usize,
-            Ships::Invader(n) => 4 + n as usize,
-            Ships::Elite(n) => 13 + n as usize,
-            Ships::Boss(0) => 4,
-            Ships::Boss(1) => 9,
-            Ships::Boss(_) => 11,
+    fn is_positive(&self) -> bool {
+        match *self {
+            Stat::Times(x) | Stat::Div(Div(x)) => x > 0.,
+        }
+    }
+}
+
+fn one() -> Stat { Stat::Times(1.) }
+fn one_u8() -> u8 { 1 }
+
+/// Hull of a [Ships] model, stats are multipliers of [base_stats]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShipTemplate {
+    #[serde(default = "one")]
+    pub health: Stat,
+    #[serde(default = "one")]
+    pub speed: Stat,
+    #[serde(default = "one")]
+    pub damage_factor: Stat,
+    #[serde(default = "one")]
+    pub shot_speed: Stat,
+    #[serde(default = "one")]
+    pub shot_frequency: Stat,
+    /// Empty for ships firing a [crate::entities::ShipPattern]
+    #[serde(default)]
+    pub weapons: Vec<(Weapons, Vec2, Targeting)>,
+    pub hitbox: Vec2,
+    /// Index in `ships.png`
+    pub sprite: usize,
+    #[serde(default = "one_u8")]
+    pub scale: u8,
+    /// Credits earned by killing the ship
+    #[serde(default)]
+    pub loot: i16,
+}
+
+impl ShipTemplate {
+    fn check(&self) -> Result<(), String> {
+        let stats = [self.health, self.speed, self.damage_factor, self.shot_speed, self.shot_frequency];
+        if !stats.iter().all(Stat::is_positive) { return Err("stats must be positive".to_string()); }
+        if self.hitbox.x <= 0. || self.hitbox.y <= 0. { return Err("empty hitbox".to_string()); }
+        if self.sprite >= SHIP_SPRITES { return Err(format!("sprite {} isn't in the atlas", self.sprite)); }
+        if self.scale == 0 { return Err("scale must be positive".to_string()); }
+        if self.loot < 0 { return Err("loot can't be negative".to_string()); }
+        Ok(())
+    }
+}
+
+/// Weapons of the main ship, chosen in the hangar independently of the hull
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeaponModule {
+    pub name: String,
+    pub weapons: Vec<(Weapons, Vec2, Targeting)>,
+    /// Fired when releasing a held fire
+    #[serde(default)]
+    pub charged: Option<ChargedShot>,
+    /// Acts to clear in a single run to unlock the module, 0 if available from the start
+    #[serde(default)]
+    pub unlock: usize,
+}
+
+impl WeaponModule {
+    fn check(&self) -> Result<(), String> {
+        if self.weapons.is_empty() { return Err("no weapons".to_string()); }
+        if let Some(charged) = &self.charged { charged.check()?; }
+        Ok(())
+    }
+}
+
+/// Hulls of every model and weapon modules of the main ship, loaded from `assets/data/fleet.ships.ron`
+#[derive(Clone, Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "e3a47c1d-5b92-4f08-8d6e-0c7f2b19a5d4"]
+#[serde(try_from = "ShipRegistryFile")]
+pub struct ShipRegistry {
+    ships: HashMap<Ships, ShipTemplate>,
+    modules: Vec<WeaponModule>,
+}
+
+/// [ShipRegistry] as written in the asset, before validation
+#[derive(Deserialize)]
+struct ShipRegistryFile {
+    ships: HashMap<Ships, ShipTemplate>,
+    modules: Vec<WeaponModule>,
+}
+
+impl TryFrom<ShipRegistryFile> for ShipRegistry {
+    type Error = String;
+
+    fn try_from(file: ShipRegistryFile) -> Result<Self, Self::Error> {
+        for (model, template) in file.ships.iter() {
+            if model.is_shield() { return Err(format!("ships: {:?} is reserved for shields", model)); }
+            template.check().map_err(|e| format!("ships: {:?}: {}", model, e))?;
+        }
+        if let Some(model) = Ships::required().find(|model| !file.ships.contains_key(model)) {
+            return Err(format!("ships: {:?} is missing", model));
         }
+        for module in file.modules.iter() {
+            module.check().map_err(|e| format!("modules: {}: {}", module.name, e))?;
+        }
+        // Every hull comes with the module of the same index, see [crate::screens::PlayableShips::module]
+        if file.modules.iter().take(PLAYABLE_SHIPS).filter(|module| module.unlock == 0).count() < PLAYABLE_SHIPS {
+            return Err(format!("modules: the first {} modules must be available from the start", PLAYABLE_SHIPS));
+        }
+        Ok(ShipRegistry { ships: file.ships, modules: file.modules })
     }
+}
+
+impl ShipRegistry {
+    /// Every model spawned is checked when loading the registry
+    pub fn get(&self, model: Ships) -> &ShipTemplate {
+        self.ships.get(&model).unwrap_or_else(|| panic!("{:?} isn't in the ship registry", model))
+    }
+
+    pub fn modules(&self) -> &[WeaponModule] { &self.modules }
+
+    /// Modules are checked when loading the registry, [index] comes from the hangar
+    pub fn module(&self, index: usize) -> &WeaponModule { &self.modules[index] }
 
-    pub fn scale(&self) -> f32 {
-        match self.model {
-            Ships::Boss(_) => 4.,
-            _ => 1.,
+    pub fn ship(&self, model: Ships) -> Ship {
+        let template = self.get(model);
+        let health = template.health.of(base_stats::HEALTH);
+        Ship {
+            model,
+            friendly: matches!(model, Ships::Player(_)),
+            health,
+            max_health: health,
+            speed: template.speed.of(base_stats::SPEED),
+            damage_factor: template.damage_factor.of(base_stats::DAMAGE_FACTOR),
+            shot_speed: template.shot_speed.of(base_stats::SHOT_SPEED),
+            shot_frequency: template.shot_frequency.of(base_stats::SHOT_FREQUENCY),
+            scale: template.scale as f32,
         }
     }
 }
@@ -325,4 +284,29 @@ fn blink(
 
         if blink.0 <= 0. { commands.entity(e).remove::<Blink>(); }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub fn registry() -> ShipRegistry {
+    ron::from_str(include_str!("../../assets/data/fleet.ships.ron")).unwrap()
+}
+
+#[test]
+fn invalid_ships_are_rejected() {
+    let ships = registry();
+    assert_eq!(ships.ship(Ships::Invader(6)).max_health, base_stats::HEALTH * 2.);
+    assert!(ships.ship(Ships::Player(1)).friendly);
+    assert_eq!(ships.ship(Ships::Boss(0)).scale, 4.);
+    assert_eq!(ships.ship(Ships::Invader(1)).speed, base_stats::SPEED / 3.);
+    assert_eq!(ships.ship(Ships::Boss(2)).speed, base_stats::SPEED / 1.1);
+
+    crate::util::assert_rejected::<ShipRegistry>(include_str!("../../assets/data/fleet.ships.ron"), &[
+        ("sprite: 16,", "sprite: 18,", "sprite 18 isn't in the atlas"),
+        ("Player(3): (", "Player(99): (", "Player(99) is reserved for shields"),
+        ("Invader(8): (", "Invader(9): (", "Invader(8) is missing"),
+        ("hitbox: (8., 4.)", "hitbox: (0., 4.)", "empty hitbox"),
+        ("health: 14.,", "health: 0.,", "stats must be positive"),
+        ("weapons: [(Beam(0), (0., 6.), Fixed(90.))],", "weapons: [],", "Laser: no weapons"),
+        ("unlock: 0,", "unlock: 1,", "must be available from the start"),
+    ]);
+}
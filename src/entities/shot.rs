@@ -1,13 +1,22 @@
-use bevy::math::vec2;
+use bevy::ecs::system::EntityCommands;
+use bevy::math::{vec2, Vec3Swizzles};
 use bevy::prelude::*;
 use bevy::sprite::collide_aabb::collide;
 use rand::{Rng, RngCore, thread_rng};
+use serde::Deserialize;
 
-use crate::entities::Ship;
-use crate::entities::weapon::{ShipWeapons, Weapon};
+use crate::entities::{HomingShot, MainShip, Ship, ShipPattern};
+use crate::entities::beam;
+use crate::entities::charge;
+use crate::entities::charge::Charging;
+use crate::entities::homing;
+use crate::entities::pattern;
+use crate::entities::pattern::{Emitter, PatternShot, Split};
+use crate::entities::weapon::{ShipWeapons, Target, Weapon};
 use crate::GameState;
 use crate::graphics::FakeTransform;
 use crate::graphics::sizes::Hitbox;
+use crate::logic::boss::BossPart;
 use crate::logic::damage::damage_ship;
 use crate::logic::hit;
 use crate::logic::hit::HitEvent;
@@ -21,9 +30,13 @@ pub struct ShotsPlugin;
 impl Plugin for ShotsPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, (shoot, update_shots, collide_shots)
+            .add_systems(Update, (shoot, homing::home_shots.before(update_shots), update_shots, beam::update_beams.after(update_shots), split_shots, collide_shots)
                 .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss, GameState::Hangar])),
             )
+            .add_systems(Update, charge::charge
+                .before(shoot)
+                .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss])),
+            )
             .add_systems(PostUpdate, damage_ship
                 .before(hit::clear_shots)
                 .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss])),
@@ -46,13 +59,14 @@ impl Shot {
     } }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Deserialize)]
 pub enum Shots {
     Bullet,
     Wave,
     Missile,
     Energy,
     DualBeam,
+    Beam,
 }
 
 impl Shots {
@@ -62,7 +76,9 @@ impl Shots {
             Shots::Wave => 1,
             Shots::Missile => 2,
             Shots::Energy => 3,
-            Shots::DualBeam => 4
+            Shots::DualBeam => 4,
+            // Drawn with a stretched bar, see [crate::entities::beam::spawn_beam]
+            Shots::Beam => 3,
         }
     }
 
@@ -73,6 +89,8 @@ impl Shots {
             Shots::Missile => Hitbox(vec2(4., 4.)),
             Shots::Energy => Hitbox(vec2(2., 4.)),
             Shots::DualBeam => Hitbox(vec2(4., 4.)),
+            // Width of the beam, its length depends on what it hits
+            Shots::Beam => Hitbox(vec2(3., 0.)),
         }
     }
 }
@@ -83,46 +101,157 @@ pub struct MuteShots;
 #[derive(Component)]
 pub struct MuteShotsFor(pub f32);
 
+/// Position of the main ship on the previous frame, kept on the ship so that each fight starts
+/// with a still target for the leading weapons
+#[derive(Component)]
+struct LastPosition(Vec2);
+
+/// Ship firing a shot, kept by the shots of a [crate::entities::pattern::Split]
+#[derive(Copy, Clone)]
+struct Shooter {
+    damage_factor: f32,
+    shot_speed: f32,
+    friendly: bool,
+    upgrades: usize,
+}
+
+/// Shot replaced by the shots of its [Split] when the timer runs out
+#[derive(Component)]
+struct SplitShot {
+    timer: f32,
+    split: Box<Split>,
+    direction: f32,
+    shooter: Shooter,
+}
+
+pub(crate) fn spawn_shot<'w, 's, 'a>(commands: &'a mut Commands<'w, 's>, textures: &Textures, weapon: Weapon, pos: Vec2, friendly: bool, upgrades: usize) -> EntityCommands<'w, 's, 'a> {
+    let mut shot = commands.spawn(SpriteSheetBundle {
+        sprite: weapon.sprite(friendly),
+        texture_atlas: textures.shots.clone(),
+        ..default()
+    });
+    shot
+        .insert(Shot::new(weapon, friendly))
+        .insert(Hitbox(weapon.shot.hitbox().0 * weapon.size.ceil()))
+        .insert(ShotUpgrades(upgrades))
+        .insert(FakeTransform {
+            translation: pos.extend(z_pos::SHOTS),
+            scale: if weapon.size > 1. { Some(Vec2::splat(weapon.size)) } else { None },
+        })
+    ;
+    shot
+}
+
+fn spawn_pattern_shot(commands: &mut Commands, textures: &Textures, shot: PatternShot, shooter: Shooter) {
+    let weapon = Weapon::pattern(shot.shot, shooter.damage_factor, shot.speed);
+    let mut entity = spawn_shot(commands, textures, weapon, shot.pos, shooter.friendly, shooter.upgrades);
+    if let Some(homing) = shot.homing { entity.insert(HomingShot::new(homing)); }
+    if let Some(split) = shot.split {
+        entity.insert(SplitShot { timer: split.after as f32 / 1000., split, direction: shot.direction, shooter });
+    }
+}
+
 fn shoot(
     mut commands: Commands,
     time: Res<Time>,
-    mut ships: Query<(&Ship, &FakeTransform, &mut ShipWeapons, Option<&ShotUpgrades>), (Without<MuteShots>, Without<MuteShotsFor>)>,
+    mut ships: Query<(Entity, &Ship, &FakeTransform, &mut ShipWeapons, Option<&mut ShipPattern>, Option<&ShotUpgrades>), (Without<MuteShots>, Without<MuteShotsFor>, Without<Charging>)>,
+    mut parts: Query<(&BossPart, &Parent, &mut ShipWeapons), Without<Ship>>,
+    mut main_ship: Query<(Entity, &FakeTransform, Option<&mut LastPosition>), With<MainShip>>,
     textures: Res<Textures>,
     mut sfx: EventWriter<PlaySFXEvent>,
 ) {
-    for (ship, ship_pos, mut weapons, upgrades) in ships.iter_mut() {
+    // Leading weapons need the velocity of the main ship
+    let dt = time.delta_seconds();
+    let aimed_at = main_ship.get_single_mut().ok().map(|(e, pos, last)| {
+        let pos = pos.translation.xy();
+        let velocity = match last {
+            Some(mut last) => {
+                let velocity = if dt > 0. { (pos - last.0) / dt } else { Vec2::ZERO };
+                last.0 = pos;
+                velocity
+            }
+            None => {
+                commands.entity(e).insert(LastPosition(pos));
+                Vec2::ZERO
+            }
+        };
+        Target { pos, velocity }
+    });
+    let target = aimed_at.map(|target| target.pos);
+    // Parts shoot with the stats of their boss, unless it is muted
+    for (part, boss, mut weapons) in parts.iter_mut() {
+        weapons.timer += time.delta_seconds();
+        let Ok((_, ship, ship_pos, ..)) = ships.get(boss.get()) else { continue; };
+        for weapon in &weapons.weapons {
+            if weapon.fires(weapons.timer, time.delta_seconds()) {
+                let pos = ship_pos.translation.xy() + part.offset + weapon.offset;
+                let aimed = weapon.aim(pos, aimed_at, weapons.timer);
+                if weapon.is_beam() { beam::spawn_beam(&mut commands, &textures, aimed, boss.get(), part.offset + weapon.offset, pos, ship.friendly, 0); }
+                else { spawn_shot(&mut commands, &textures, aimed, pos, ship.friendly, 0); }
+                sfx.send(PlaySFXEvent(SFX::EnemyFire));
+            }
+        }
+    }
+    for (e, ship, ship_pos, mut weapons, pattern, upgrades) in ships.iter_mut() {
         weapons.timer += time.delta_seconds();
         let mut fired = false;
         if !((-8.)..(WIDTH as f32 + 8.)).contains(&ship_pos.translation.x) { continue; }
+        let upgrades = upgrades.map_or(0, |u| u.0);
+        let aimed_at = if ship.friendly { None } else { aimed_at };
         for weapon in &weapons.weapons {
             if weapon.fires(weapons.timer, time.delta_seconds()) {
                 fired = true;
-                commands
-                    .spawn(SpriteSheetBundle {
-                        sprite: weapon.sprite(ship.friendly),
-                        texture_atlas: textures.shots.clone(),
-                        ..default()
-                    })
-                    .insert(Shot::new(weapon.clone(), ship.friendly))
-                    .insert(weapon.shot.hitbox())
-                    .insert(ShotUpgrades(match upgrades {
-                        Some(u) => u.0,
-                        None => 0,
-                    }))
-                    .insert(FakeTransform::from_xyz(
-                        ship_pos.translation.x + weapon.offset.x,
-                        ship_pos.translation.y + weapon.offset.y,
-                        z_pos::SHOTS,
-                    ))
-                ;
+                let pos = ship_pos.translation.xy() + weapon.offset;
+                let aimed = weapon.aim(pos, aimed_at, weapons.timer);
+                if weapon.is_beam() { beam::spawn_beam(&mut commands, &textures, aimed, e, weapon.offset, pos, ship.friendly, upgrades); }
+                else { spawn_shot(&mut commands, &textures, aimed, pos, ship.friendly, upgrades); }
             }
         }
+        if let Some(mut pattern) = pattern {
+            let emitter = Emitter {
+                pos: ship_pos.translation.xy(),
+                target: if ship.friendly { None } else { target },
+                facing: if ship.friendly { 90. } else { 270. },
+                shot_speed: ship.shot_speed,
+            };
+            let shooter = Shooter { damage_factor: ship.damage_factor, shot_speed: ship.shot_speed, friendly: ship.friendly, upgrades };
+            let mut shots = vec![];
+            pattern.step(time.delta_seconds() * ship.shot_frequency, &emitter, &mut shots);
+            fired |= !shots.is_empty();
+            for shot in shots { spawn_pattern_shot(&mut commands, &textures, shot, shooter); }
+        }
         if fired {
             sfx.send(PlaySFXEvent(if ship.friendly { SFX::ShipFire } else { SFX::EnemyFire }));
         }
     }
 }
 
+fn split_shots(
+    mut commands: Commands,
+    time: Res<Time>,
+    textures: Res<Textures>,
+    mut shots: Query<(Entity, &mut SplitShot, &FakeTransform)>,
+    main_ship: Query<&FakeTransform, (With<MainShip>, Without<SplitShot>)>,
+) {
+    let target = main_ship.get_single().ok().map(|pos| pos.translation.xy());
+    for (e, mut split, pos) in shots.iter_mut() {
+        split.timer -= time.delta_seconds();
+        if split.timer > 0. { continue; }
+        commands.entity(e).despawn_recursive();
+
+        let emitter = Emitter {
+            pos: pos.translation.xy(),
+            target: if split.shooter.friendly { None } else { target },
+            facing: split.direction,
+            shot_speed: split.shooter.shot_speed,
+        };
+        let mut previous = split.direction;
+        let mut shots = vec![];
+        pattern::fire(&split.split.then, &mut previous, &emitter, &mut shots);
+        for shot in shots { spawn_pattern_shot(&mut commands, &textures, shot, split.shooter); }
+    }
+}
+
 fn update_shots(
     mut commands: Commands,
     time: Res<Time>,
@@ -147,14 +276,29 @@ fn collide_shots(
     mut commands: Commands,
     mut shots: Query<(&mut Shot, &Hitbox, &ShotUpgrades, &FakeTransform, Entity)>,
     ships: Query<(&Ship, &Hitbox, &FakeTransform, Entity)>,
+    parts: Query<(&BossPart, &Parent, &Hitbox, Entity)>,
     mut event_writer: EventWriter<HitEvent>,
     mut sfx: EventWriter<PlaySFXEvent>,
 ) {
     let mut rng = thread_rng();
 
     for (mut shot, shot_hitbox, upgrades, shot_pos, shot_entity) in shots.iter_mut() {
-        for (ship, ship_hitbox, ship_pos, ship_entity) in &ships {
+        // Parts cover their boss
+        let mut covered = vec![];
+        for (part, boss, part_hitbox, part_entity) in &parts {
+            let Ok((ship, _, ship_pos, _)) = ships.get(boss.get()) else { continue; };
             if shot.friendly == ship.friendly { continue; }
+            if upgrades.0 & PIERCING != 0 && shot.collisions.contains(&part_entity) { continue }
+            let part_pos = ship_pos.translation + part.offset.extend(0.);
+            if collide(shot_pos.translation, shot_hitbox.0, part_pos, part_hitbox.0).is_some() {
+                covered.push(boss.get());
+                shot.collisions.push(part_entity);
+                event_writer.send(HitEvent { shot: shot_entity, ship: boss.get(), part: Some(part_entity) });
+            }
+        }
+
+        for (ship, ship_hitbox, ship_pos, ship_entity) in &ships {
+            if shot.friendly == ship.friendly || covered.contains(&ship_entity) { continue; }
             if upgrades.0 & PIERCING != 0 && shot.collisions.contains(&ship_entity) { continue }
             let collision = collide(
                 shot_pos.translation,
@@ -172,7 +316,7 @@ fn collide_shots(
                     }
                 }
                 shot.collisions.push(ship_entity);
-                event_writer.send(HitEvent { shot: shot_entity, ship: ship_entity });
+                event_writer.send(HitEvent { shot: shot_entity, ship: ship_entity, part: None });
             }
         }
     }
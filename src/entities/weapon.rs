@@ -1,18 +1,23 @@
+use std::f32::consts::PI;
+
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
+use serde::Deserialize;
 
 use crate::entities::Ship;
 use crate::entities::shot::Shots;
 use crate::graphics::Palette;
 use crate::util::Angle;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Deserialize)]
 pub enum Weapons {
     Standard,
     Wave,
     Missile,
     Energy,
     Dual,
+    /// Continuous [crate::entities::beam::Beam], telegraphed for a number of milliseconds before dealing damage
+    Beam(usize),
 }
 
 impl Weapons {
@@ -23,22 +28,58 @@ impl Weapons {
             Weapons::Missile => Shots::Missile,
             Weapons::Energy => Shots::Energy,
             Weapons::Dual => Shots::DualBeam,
+            Weapons::Beam(_) => Shots::Beam,
+        }
+    }
+
+    /// In seconds
+    pub fn telegraph(&self) -> f32 {
+        match self {
+            Weapons::Beam(telegraph) => *telegraph as f32 / 1000.,
+            _ => 0.,
         }
     }
 }
 
+/// Direction of the shots of a [Weapon], enemies aim at the main ship
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum Targeting {
+    Fixed(Angle),
+    /// At the position of the target when firing
+    Aimed,
+    /// Where the target will be if it keeps its velocity
+    Leading,
+    /// Back and forth between two angles, over a period in seconds
+    Sweep(Angle, Angle, f32),
+}
+
+/// Position and velocity of the ship aimed at
+#[derive(Copy, Clone)]
+pub struct Target {
+    pub pos: Vec2,
+    pub velocity: Vec2,
+}
+
 #[derive(Copy, Clone)]
 pub struct Weapon {
     pub shot: Shots,
     pub attack: f32,
+    /// Set when firing, see [Weapon::aim]
     pub speed: Vec2,
     pub offset: Vec2,
+    targeting: Targeting,
     delay: f32,
+    /// Seconds a beam is shown before dealing damage
+    pub telegraph: f32,
+    /// Scale of the sprite and hitbox of the shots
+    pub size: f32,
 }
 
 impl Weapon {
     pub fn fires(&self, timer: f32, delta: f32) -> bool { ((timer - delta) % self.delay) > timer % self.delay }
 
+    pub fn is_beam(&self) -> bool { matches!(self.shot, Shots::Beam) }
+
     pub fn sprite(&self, friendly: bool) -> TextureAtlasSprite {
         TextureAtlasSprite {
             index: self.shot.sprite_atlas_index(),
@@ -51,13 +92,51 @@ impl Weapon {
 }
 
 impl Weapon {
-    pub(crate) fn new(model: Shots, ship: &Ship, offset: Vec2, angle: Angle) -> Self {
+    pub(crate) fn new(model: Shots, ship: &Ship, offset: Vec2, targeting: Targeting) -> Self {
+        let angle = match targeting {
+            Targeting::Fixed(angle) | Targeting::Sweep(angle, _, _) => angle,
+            // Straight ahead without a target
+            Targeting::Aimed | Targeting::Leading => Angle(if ship.friendly { 90. } else { 270. }),
+        };
         Weapon {
             shot: model,
             attack: model.attack() * ship.damage_factor,
             speed: angle.rotate(ship.shot_speed),
             offset,
+            targeting,
             delay: model.delay() / ship.shot_frequency,
+            telegraph: 0.,
+            size: 1.,
+        }
+    }
+
+    /// Weapon firing from [from] after [timer] seconds, its speed pointing to the target
+    pub fn aim(mut self, from: Vec2, target: Option<Target>, timer: f32) -> Self {
+        let speed = self.speed.length();
+        let direction = match (self.targeting, target) {
+            (Targeting::Fixed(_), _) | (Targeting::Aimed | Targeting::Leading, None) => return self,
+            (Targeting::Aimed, Some(target)) => target.pos - from,
+            (Targeting::Leading, Some(target)) => lead(target.pos - from, target.velocity, speed),
+            (Targeting::Sweep(Angle(a), Angle(b), period), _) => {
+                let progress = 0.5 - 0.5 * (timer / period * 2. * PI).cos();
+                Angle(a + (b - a) * progress).rotate(1.)
+            }
+        };
+        if direction != Vec2::ZERO { self.speed = direction.normalize() * speed; }
+        self
+    }
+
+    /// Single shot fired by a [crate::entities::ShipPattern], it never fires by itself
+    pub(crate) fn pattern(model: Shots, damage_factor: f32, speed: Vec2) -> Self {
+        Weapon {
+            shot: model,
+            attack: model.attack() * damage_factor,
+            speed,
+            offset: Vec2::ZERO,
+            targeting: Targeting::Fixed(Angle(0.)),
+            delay: f32::INFINITY,
+            telegraph: 0.,
+            size: 1.,
         }
     }
 }
@@ -69,15 +148,65 @@ pub struct ShipWeapons {
 }
 
 impl ShipWeapons {
-    pub fn new(ship: &Ship, weapons: Vec<(Weapons, Vec2, Angle)>) -> Self {
+    pub fn new(ship: &Ship, weapons: Vec<(Weapons, Vec2, Targeting)>) -> Self {
         ShipWeapons {
-            weapons: weapons.iter().map(|(w, offset, angle)| Weapon::new(
-                w.shot_type(),
-                ship,
-                offset.clone(),
-                *angle,
-            )).collect(),
+            weapons: weapons.iter().map(|(w, offset, targeting)| Weapon {
+                telegraph: w.telegraph(),
+                ..Weapon::new(w.shot_type(), ship, offset.clone(), *targeting)
+            }).collect(),
             timer: 0.,
         }
     }
-}
\ No newline at end of file
+
+    /// Shoot [rate] times as often
+    pub fn with_fire_rate(mut self, rate: f32) -> Self {
+        for weapon in self.weapons.iter_mut() { weapon.delay /= rate; }
+        self
+    }
+}
+
+/// Direction hitting a target at [offset] moving at [velocity] with shots at [speed],
+/// straight at the target if the shots can't catch up
+fn lead(offset: Vec2, velocity: Vec2, speed: f32) -> Vec2 {
+    // |offset + velocity * t| = speed * t
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2. * offset.dot(velocity);
+    let c = offset.length_squared();
+    let t = if a.abs() < 0.001 { -c / b } else {
+        let discriminant = b * b - 4. * a * c;
+        if discriminant < 0. { return offset; }
+        [(-b - discriminant.sqrt()) / (2. * a), (-b + discriminant.sqrt()) / (2. * a)]
+            .into_iter()
+            .filter(|&t| t > 0.)
+            .fold(f32::INFINITY, f32::min)
+    };
+    if t > 0. && t.is_finite() { offset + velocity * t } else { offset }
+}
+
+#[test]
+fn weapons_aim_at_the_target() {
+    let ship = Ship { shot_speed: 100., shot_frequency: 1., damage_factor: 1., ..default() };
+    let from = Vec2::new(80., 100.);
+    let target = Some(Target { pos: Vec2::new(40., 20.), velocity: Vec2::new(30., 0.) });
+
+    let fixed = Weapon::new(Shots::Bullet, &ship, Vec2::ZERO, Targeting::Fixed(Angle(270.)));
+    assert!(fixed.aim(from, target, 0.).speed.abs_diff_eq(Vec2::new(0., -100.), 0.01));
+    let aimed = Weapon::new(Shots::Bullet, &ship, Vec2::ZERO, Targeting::Aimed).aim(from, target, 0.);
+    assert!(aimed.speed.normalize().abs_diff_eq((Vec2::new(40., 20.) - from).normalize(), 0.001));
+    assert!(Weapon::new(Shots::Bullet, &ship, Vec2::ZERO, Targeting::Aimed).aim(from, None, 0.).speed.y < 0.);
+
+    // The leading shot meets the target
+    let leading = Weapon::new(Shots::Bullet, &ship, Vec2::ZERO, Targeting::Leading).aim(from, target, 0.);
+    let t = (0..300).map(|i| i as f32 / 100.)
+        .min_by(|&a, &b| {
+            let distance = |t: f32| (from + leading.speed * t).distance(Vec2::new(40. + 30. * t, 20.));
+            distance(a).total_cmp(&distance(b))
+        })
+        .unwrap();
+    assert!((from + leading.speed * t).distance(Vec2::new(40. + 30. * t, 20.)) < 1.);
+    assert_eq!(leading.speed.length().round(), 100.);
+
+    let sweep = Weapon::new(Shots::Bullet, &ship, Vec2::ZERO, Targeting::Sweep(Angle(225.), Angle(315.), 2.));
+    assert!(sweep.aim(from, None, 0.).speed.abs_diff_eq(Angle(225.).rotate(100.), 0.01));
+    assert!(sweep.aim(from, None, 1.).speed.abs_diff_eq(Angle(315.).rotate(100.), 0.01));
+}
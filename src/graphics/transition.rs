@@ -55,7 +55,10 @@ pub fn update(
                 1 => {
                     if state == GameState::SimpleText { play_bgm.send(PlayBGMEvent(BGM::None)); }
                     else {
-                        let non_dummy = if let Some(r) = route { r.state() } else { state };
+                        let non_dummy = match route {
+                            Some(r) if state == GameState::Dummy => r.state(),
+                            _ => state,
+                        };
                         if let Some(bgm) = non_dummy.bgm() { play_bgm.send(PlayBGMEvent(bgm)); }
                     }
                 }
@@ -71,7 +74,7 @@ pub fn update(
             match transition.clock {
                 1 => {
                     if let Some(route) = route {
-                        time.set_relative_speed(space::time_ratio(route.level));
+                        time.set_relative_speed(space::time_ratio(route.loop_level(), route.loops()));
                     } else {
                         time.set_relative_speed(1.0);
                     }
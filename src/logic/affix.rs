@@ -0,0 +1,250 @@
+use bevy::math::vec2;
+use bevy::prelude::*;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::entities::{Blink, BulletPatterns, Ship, ShipRegistry, ShipWeapons};
+use crate::graphics::FakeTransform;
+use crate::logic::damage::{DamageEvent, Dead};
+use crate::logic::movement::{Movement, Moves};
+use crate::logic::rng::RunRng;
+use crate::logic::wave;
+use crate::logic::WaveTemplates;
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::{Data, Textures};
+use crate::util::{affixes, space, WIDTH};
+
+/// Modifiers rolled by elites on top of their usual behaviour
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Affix {
+    /// Barrier absorbing damage, coming back after a while once broken
+    Shielded,
+    /// Splits into two invaders on death, the fight ends once they are killed
+    Splitting,
+    /// Shoots faster as its health drops
+    Enraged,
+    Regenerating,
+    /// Jumps away from its path from time to time
+    Teleporting,
+}
+
+impl Affix {
+    pub const ALL: [Affix; 5] = [Affix::Shielded, Affix::Splitting, Affix::Enraged, Affix::Regenerating, Affix::Teleporting];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Affix::Shielded => "Shielded",
+            Affix::Splitting => "Splitting",
+            Affix::Enraged => "Enraged",
+            Affix::Regenerating => "Regen",
+            Affix::Teleporting => "Teleport",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Affixes {
+    pub affixes: Vec<Affix>,
+    /// Multiplier of the effects, growing with the act
+    strength: f32,
+    act: usize,
+    loops: usize,
+    barrier: f32,
+    /// Seconds until the barrier comes back
+    barrier_cooldown: f32,
+    /// Seconds until the next teleport
+    teleport_timer: f32,
+    /// Horizontal offset of a teleporting elite from its path
+    shift: f32,
+    /// Teleports and splits draw from it rather than from the run, whose draws would otherwise
+    /// depend on when the player hits the elite
+    rng: RunRng,
+}
+
+impl Affixes {
+    /// [act] starts at 0
+    pub fn roll(act: usize, loops: usize, rng: &mut RunRng) -> Self {
+        let n = (act + 1).min(affixes::MAX_AFFIXES);
+        let affixes = Affix::ALL.choose_multiple(rng, n).copied().collect();
+        Self::new(affixes, act, loops, rng.gen())
+    }
+
+    fn new(affixes: Vec<Affix>, act: usize, loops: usize, seed: u64) -> Self {
+        let strength = 1. + act as f32 * affixes::ACT_SCALING;
+        Affixes {
+            barrier: if affixes.contains(&Affix::Shielded) { affixes::BARRIER_HEALTH * strength } else { 0. },
+            affixes,
+            strength,
+            act,
+            loops,
+            barrier_cooldown: 0.,
+            teleport_timer: affixes::TELEPORT_INTERVAL / strength,
+            shift: 0.,
+            rng: RunRng::new(seed),
+        }
+    }
+
+    pub fn has(&self, affix: Affix) -> bool { self.affixes.contains(&affix) }
+
+    pub fn text(&self) -> String {
+        self.affixes.iter().map(|affix| affix.name()).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn credits(&self) -> i16 { self.affixes.len() as i16 * affixes::CREDITS }
+
+    /// Returns the damage going through the barrier
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        if self.barrier < 0.001 { return damage; }
+        let absorbed = damage.min(self.barrier);
+        self.barrier -= absorbed;
+        if self.barrier < 0.001 { self.barrier_cooldown = affixes::BARRIER_COOLDOWN; }
+        damage - absorbed
+    }
+
+    pub fn fire_rate(&self, health_ratio: f32) -> f32 {
+        if !self.has(Affix::Enraged) { return 1.; }
+        1. + (1. - health_ratio) * affixes::ENRAGED_FIRE_RATE * self.strength
+    }
+
+    fn regenerate(&mut self, ship: &mut Ship, dt: f32) {
+        if self.has(Affix::Regenerating) {
+            ship.health = (ship.health + ship.max_health * affixes::REGENERATION * self.strength * dt).min(ship.max_health);
+        }
+        if self.has(Affix::Shielded) && self.barrier < 0.001 {
+            self.barrier_cooldown -= dt;
+            if self.barrier_cooldown <= 0. { self.barrier = affixes::BARRIER_HEALTH * self.strength; }
+        }
+    }
+}
+
+/// Barrier drawn under a shielded elite, child of the elite
+#[derive(Component)]
+pub struct Barrier;
+
+/// Invader an elite split into
+#[derive(Component)]
+pub struct EliteSplit;
+
+pub fn spawn_barriers(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    elites: Query<(Entity, &Affixes), Added<Affixes>>,
+) {
+    for (e, affixes) in elites.iter() {
+        if !affixes.has(Affix::Shielded) { continue; }
+        let barrier = commands
+            .spawn(SpriteBundle {
+                texture: textures.shield.clone(),
+                sprite: Sprite { flip_y: true, ..default() },
+                transform: Transform::from_xyz(0., -space::SHIELD_OFFSET, 1.),
+                ..default()
+            })
+            .insert(Barrier)
+            .id();
+        commands.entity(e).add_child(barrier);
+    }
+}
+
+/// Regenerates the elites and their barriers, and moves the teleporting ones after [crate::logic::movement::apply_movement]
+pub fn update_affixes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut elites: Query<(Entity, &mut Ship, &mut Affixes, &mut FakeTransform, Option<&Children>), Without<Dead>>,
+    mut barriers: Query<&mut Visibility, With<Barrier>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    let dt = time.delta_seconds();
+    for (e, mut ship, mut affixes, mut pos, children) in elites.iter_mut() {
+        affixes.regenerate(&mut ship, dt);
+
+        for &child in children.into_iter().flatten() {
+            let Ok(mut visibility) = barriers.get_mut(child) else { continue; };
+            visibility.set_if_neq(if affixes.barrier > 0.001 { Visibility::Inherited } else { Visibility::Hidden });
+        }
+
+        if affixes.has(Affix::Teleporting) {
+            affixes.teleport_timer -= dt;
+            if affixes.teleport_timer <= 0. {
+                affixes.teleport_timer = affixes::TELEPORT_INTERVAL / affixes.strength;
+                affixes.shift = affixes.rng.gen_range(-affixes::TELEPORT_RANGE..affixes::TELEPORT_RANGE);
+                commands.entity(e).insert(Blink(space::BLINK_INTERVAL * 2.));
+                sfx.send(PlaySFXEvent(SFX::Dash));
+            }
+            // Stay on screen whatever the path does
+            pos.translation.x = (pos.translation.x + affixes.shift).clamp(16., WIDTH as f32 - 16.);
+        }
+    }
+}
+
+/// Enraged elites shoot faster after each hit
+pub fn enrage(
+    mut damage: EventReader<DamageEvent>,
+    mut elites: Query<(&Ship, &Affixes, &mut ShipWeapons)>,
+    data: Res<Data>,
+    ships: Res<Assets<ShipRegistry>>,
+) {
+    let ships = ships.get(&data.ships).unwrap();
+    for event in damage.iter() {
+        let Ok((ship, affixes, mut weapons)) = elites.get_mut(event.ship) else { continue; };
+        // Ships firing a pattern have no weapons to speed up
+        if !affixes.has(Affix::Enraged) || weapons.weapons.is_empty() { continue; }
+        let timer = weapons.timer;
+        *weapons = ShipWeapons::new(ship, ships.get(ship.model).weapons.clone()).with_fire_rate(affixes.fire_rate(ship.health / ship.max_health));
+        weapons.timer = timer;
+    }
+}
+
+/// See [crate::logic::damage::die_gracefully] for the end of the fight
+pub fn split_elites(
+    mut commands: Commands,
+    time: Res<Time>,
+    textures: Res<Textures>,
+    mut damage: EventReader<DamageEvent>,
+    mut elites: Query<(&mut Affixes, &FakeTransform)>,
+    data: Res<Data>,
+    waves: Res<Assets<WaveTemplates>>,
+    patterns: Res<Assets<BulletPatterns>>,
+    ships: Res<Assets<ShipRegistry>>,
+) {
+    let (waves, patterns, ships) = (waves.get(&data.waves).unwrap(), patterns.get(&data.patterns).unwrap(), ships.get(&data.ships).unwrap());
+    for event in damage.iter() {
+        if !event.fatal { continue; }
+        let Ok((mut affixes, pos)) = elites.get_mut(event.ship) else { continue; };
+        if !affixes.has(Affix::Splitting) { continue; }
+        for side in [-1., 1.] {
+            let act = affixes.act;
+            let model = waves.random_enemy(act, &mut affixes.rng);
+            let start = vec2((pos.translation.x + side * 16.).clamp(16., WIDTH as f32 - 16.), pos.translation.y);
+            wave::spawn_ship(&mut commands, &textures, ships, patterns, model, start, affixes.loops)
+                .insert(Movement { moves: Moves::Lemniscate(start, 1.5, 12.), t_0: time.elapsed_seconds() })
+                .insert(EliteSplit);
+        }
+    }
+}
+
+#[test]
+fn affixes_scale_with_act() {
+    let mut rng = RunRng::new(42);
+    assert_eq!(Affixes::roll(0, 0, &mut rng).affixes.len(), 1);
+    let affixes = Affixes::roll(2, 0, &mut rng);
+    assert_eq!(affixes.affixes.len(), 2);
+    assert_ne!(affixes.affixes[0], affixes.affixes[1]);
+    assert_eq!(affixes.credits(), 2 * affixes::CREDITS);
+
+    // Rolling always takes the same draws, whatever happens during the fight
+    let (mut elite, mut again) = (Affixes::roll(1, 0, &mut RunRng::new(7)), Affixes::roll(1, 0, &mut RunRng::new(7)));
+    assert_eq!(elite.affixes, again.affixes);
+    assert_eq!(elite.rng.gen::<u64>(), again.rng.gen::<u64>());
+
+    let mut shielded = Affixes::new(vec![Affix::Shielded, Affix::Enraged], 2, 0, 0);
+    assert_eq!(shielded.absorb(10.), 0.);
+    assert_eq!(shielded.absorb(10.), 10. - (affixes::BARRIER_HEALTH * 1.5 - 10.));
+    assert_eq!(shielded.absorb(3.), 3.);
+    assert!(shielded.fire_rate(0.) > Affixes::new(vec![Affix::Enraged], 0, 0, 0).fire_rate(0.));
+    assert_eq!(shielded.fire_rate(1.), 1.);
+
+    // The barrier comes back after its cooldown
+    let mut ship = crate::entities::registry().ship(crate::entities::Ships::Elite(0));
+    shielded.regenerate(&mut ship, affixes::BARRIER_COOLDOWN + 0.1);
+    assert_eq!(shielded.absorb(1.), 0.);
+}
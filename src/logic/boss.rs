@@ -0,0 +1,248 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+
+use crate::entities::{Blink, MuteShotsFor, Ship, Ships, ShipWeapons, Targeting, Weapons};
+use crate::graphics::FakeTransform;
+use crate::graphics::sizes::Hitbox;
+use crate::logic::damage::Dead;
+use crate::logic::elite;
+use crate::logic::movement::{Movement, Moves};
+use crate::logic::wave::WaveEvent;
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::Textures;
+use crate::util::space;
+
+/// Behaviour of a boss until its health gets below the threshold of the next phase
+pub struct BossPhase {
+    /// Ratio of the max health below which the phase starts, 1 for the first phase
+    pub from: f32,
+    /// Movement of the phase, the boss glides to its starting position
+    pub moves: Moves,
+    pub weapons: Vec<(Weapons, Vec2, Targeting)>,
+    /// Multiplier of the shot frequency of the boss
+    pub fire_rate: f32,
+    /// Track of the wave started with the phase, joining the adds of the previous phases
+    pub adds: Vec<WaveEvent>,
+}
+
+#[derive(Component)]
+pub struct BossPhases {
+    phases: Vec<BossPhase>,
+    /// Phase applied to the boss, [None] until it spawned
+    current: Option<usize>,
+}
+
+impl BossPhases {
+    pub fn new(phases: Vec<BossPhase>) -> Self { BossPhases { phases, current: None } }
+
+    /// Health ratios at which the phases after the first one start
+    pub fn thresholds(&self) -> impl Iterator<Item=f32> + '_ {
+        self.phases.iter().skip(1).map(|phase| phase.from)
+    }
+
+    /// Phases never go back, a big hit can skip one
+    fn phase_at(&self, health_ratio: f32) -> usize {
+        let phase = self.phases.iter().rposition(|phase| health_ratio < phase.from).unwrap_or(0);
+        phase.max(self.current.unwrap_or(0))
+    }
+}
+
+/// Invulnerability while switching phases
+#[derive(Component)]
+pub struct PhaseTransition(pub f32);
+
+pub fn update_phases(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut bosses: Query<(Entity, &Ship, Option<&BossParts>, &mut BossPhases, &mut ShipWeapons, &mut Movement, &FakeTransform), Without<Dead>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    for (e, ship, parts, mut phases, mut weapons, mut movement, pos) in bosses.iter_mut() {
+        let phase = phases.phase_at(health_ratio(ship, parts));
+        if phases.current == Some(phase) { continue; }
+        let first = phases.current.is_none();
+        phases.current = Some(phase);
+
+        let BossPhase { moves, weapons: phase_weapons, fire_rate, .. } = &phases.phases[phase];
+        *weapons = ShipWeapons::new(ship, phase_weapons.clone()).with_fire_rate(*fire_rate);
+        // The movement of the first phase comes after the entrance of the boss
+        if first { continue; }
+
+        info!("{:?} – phase {} at {:.0}/{:.0} health", ship.model, phase + 1, ship.health, ship.max_health);
+        movement.moves = Moves::MoveTo(pos.translation.xy(), *moves.starting_pos(), 0., Box::new(moves.clone()));
+        movement.t_0 = time.elapsed_seconds();
+        commands
+            .entity(e)
+            .insert(PhaseTransition(space::PHASE_TRANSITION))
+            .insert(MuteShotsFor(space::PHASE_TRANSITION))
+            .insert(Blink(space::PHASE_TRANSITION))
+        ;
+        sfx.send(PlaySFXEvent(SFX::Shield));
+    }
+}
+
+pub fn end_transitions(
+    mut commands: Commands,
+    mut transitions: Query<(Entity, &mut PhaseTransition)>,
+    time: Res<Time>,
+) {
+    for (e, mut transition) in transitions.iter_mut() {
+        transition.0 -= time.delta_seconds();
+        if transition.0 <= 0. { commands.entity(e).remove::<PhaseTransition>(); }
+    }
+}
+
+/// Destructible part of a boss, drawn on top of it
+pub struct PartTemplate {
+    /// From the center of the boss, in pixels, a multiple of the scale of the boss
+    pub offset: Vec2,
+    pub hitbox: Vec2,
+    /// Index in `ships.png`, drawn without the scale of the boss
+    pub sprite: usize,
+    /// Ratio of the max health of the boss
+    pub health: f32,
+    pub weapons: Vec<(Weapons, Vec2, Targeting)>,
+    /// Parts to destroy before this one can be damaged
+    pub after: Vec<usize>,
+    /// The core of the boss can't be damaged while the part stands
+    pub shields_core: bool,
+}
+
+/// Part of a boss, child of the boss entity
+#[derive(Component)]
+pub struct BossPart {
+    index: usize,
+    pub offset: Vec2,
+    health: f32,
+    after: Vec<usize>,
+}
+
+/// Parts of a boss, on the boss entity
+#[derive(Component)]
+pub struct BossParts {
+    /// Parts still standing, by index
+    standing: Vec<bool>,
+    shields_core: Vec<bool>,
+    /// Total of the parts still standing
+    health: f32,
+    max_health: f32,
+}
+
+impl BossParts {
+    pub fn vulnerable(&self, part: &BossPart) -> bool { part.after.iter().all(|&i| !self.standing[i]) }
+
+    pub fn shields_core(&self) -> bool {
+        self.standing.iter().zip(self.shields_core.iter()).any(|(&standing, &shields)| standing && shields)
+    }
+
+    /// Returns true if the part is destroyed
+    pub fn damage(&mut self, part: &mut BossPart, damage: f32) -> bool {
+        if !self.standing[part.index] { return false; }
+        let damage = damage.min(part.health);
+        part.health -= damage;
+        self.health -= damage;
+        if part.health < 0.001 { self.standing[part.index] = false; }
+        !self.standing[part.index]
+    }
+}
+
+/// Health ratio of a boss, counting the parts still standing
+pub fn health_ratio(ship: &Ship, parts: Option<&BossParts>) -> f32 {
+    match parts {
+        Some(parts) => (ship.health + parts.health) / (ship.max_health + parts.max_health),
+        None => ship.health / ship.max_health,
+    }
+}
+
+/// Add the parts of the bosses once they are spawned, see [elite::boss_parts]
+pub fn spawn_parts(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    bosses: Query<(Entity, &Ship), Added<BossPhases>>,
+) {
+    for (e, ship) in bosses.iter() {
+        let Ships::Boss(n) = ship.model else { continue; };
+        let templates = elite::boss_parts(n);
+        let mut parts = BossParts { standing: vec![], shields_core: vec![], health: 0., max_health: 0. };
+        for (index, template) in templates.into_iter().enumerate() {
+            let health = template.health * ship.max_health;
+            parts.standing.push(true);
+            parts.shields_core.push(template.shields_core);
+            parts.health += health;
+            parts.max_health += health;
+
+            let scale = ship.scale;
+            let part = commands
+                .spawn(SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        index: template.sprite,
+                        ..default()
+                    },
+                    texture_atlas: textures.ship.clone(),
+                    transform: Transform::from_translation((template.offset / scale).extend(1.))
+                        .with_scale(Vec3::splat(1. / scale)),
+                    ..default()
+                })
+                .insert(BossPart { index, offset: template.offset, health, after: template.after })
+                .insert(Hitbox(template.hitbox))
+                .insert(ShipWeapons::new(ship, template.weapons))
+                .id();
+            commands.entity(e).add_child(part);
+        }
+        commands.entity(e).insert(parts);
+    }
+}
+
+#[cfg(test)]
+fn phases(thresholds: &[f32]) -> BossPhases {
+    BossPhases::new(thresholds.iter()
+        .map(|&from| BossPhase { from, moves: Moves::Linear(Vec2::ZERO, crate::util::Angle(0.)), weapons: vec![], fire_rate: 1., adds: vec![] })
+        .collect())
+}
+
+#[test]
+fn phases_follow_health_thresholds() {
+    let mut phases = phases(&[1., 0.66, 0.33]);
+    assert_eq!(phases.thresholds().collect::<Vec<f32>>(), vec![0.66, 0.33]);
+    assert_eq!(phases.phase_at(1.), 0);
+    assert_eq!(phases.phase_at(0.7), 0);
+    assert_eq!(phases.phase_at(0.5), 1);
+    assert_eq!(phases.phase_at(0.1), 2);
+
+    // Healing doesn't go back to a previous phase
+    phases.current = Some(2);
+    assert_eq!(phases.phase_at(0.9), 2);
+}
+
+#[test]
+fn parts_shield_the_core() {
+    let part = |index: usize, after: Vec<usize>| BossPart { index, offset: Vec2::ZERO, health: 4., after };
+    let mut parts = BossParts { standing: vec![true; 3], shields_core: vec![true, true, false], health: 12., max_health: 12. };
+    let (mut left, mut right, mut center) = (part(0, vec![]), part(1, vec![]), part(2, vec![0, 1]));
+    assert!(!parts.vulnerable(&center));
+    assert!(parts.shields_core());
+
+    assert!(!parts.damage(&mut left, 3.));
+    assert!(parts.damage(&mut left, 3.));
+    assert!(parts.shields_core());
+    assert!(parts.damage(&mut right, 10.));
+    assert!(!parts.shields_core());
+    assert!(parts.vulnerable(&center));
+    assert_eq!(parts.health, 4.);
+    assert!(!parts.damage(&mut left, 3.));
+    assert!(parts.damage(&mut center, 4.));
+    assert_eq!(parts.health, 0.);
+}
+
+#[test]
+fn boss_parts_are_drawn_on_pixels() {
+    let ships = crate::entities::registry();
+    for n in 0..3 {
+        let parts = elite::boss_parts(n);
+        let scale = ships.ship(Ships::Boss(n)).scale;
+        for part in parts.iter() {
+            assert_eq!(part.offset / scale, (part.offset / scale).round());
+            assert!(part.after.iter().all(|&i| i < parts.len()));
+        }
+    }
+}
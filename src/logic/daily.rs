@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Resource;
+use instant::SystemTime;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::logic::Items;
+use crate::logic::saves;
+use crate::screens::PlayableShips;
+
+/// Save file keeping the best result of each daily challenge
+const SCORES_PATH: &str = "daily.ron";
+
+/// Daily challenge, everyone playing on the same day gets the same seed, ship and items
+#[derive(Resource, Copy, Clone)]
+pub struct Daily {
+    /// Days since the Unix epoch (UTC)
+    pub day: u64,
+}
+
+/// Best result of a daily challenge, ordered from worst to best
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
+pub struct DailyScore {
+    pub win: bool,
+    pub act: usize,
+    /// Level reached in the act, starting at 1
+    pub level: usize,
+}
+
+impl Daily {
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Daily { day: secs / 86400 }
+    }
+
+    /// Short enough to be typed in from the hangar, like [crate::logic::rng::RunRng::random_seed]
+    pub fn seed(&self) -> u64 { self.day.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32 }
+
+    fn rng(&self) -> StdRng { StdRng::seed_from_u64(!self.seed()) }
+
+    pub fn ship(&self) -> PlayableShips {
+        let mut ship = PlayableShips::Ship1;
+        for _ in 0..self.rng().gen_range(0..4) { ship = ship.next(); }
+        ship
+    }
+
+    /// Items given on top of [crate::util::items::STARTING_ITEMS]
+    pub fn bonus_items(&self) -> Vec<(Items, usize)> {
+        let mut rng = self.rng();
+        // Skip the draw of the ship
+        rng.gen_range(0..4);
+        vec![(Items::Missile, rng.gen_range(0..=3)), (Items::Shield, rng.gen_range(0..=3))]
+    }
+
+    fn scores() -> HashMap<u64, DailyScore> {
+        saves::load(SCORES_PATH).unwrap_or_default()
+    }
+
+    /// Best result for this day, [None] if it wasn't played yet
+    pub fn score(&self) -> Option<DailyScore> { Daily::scores().get(&self.day).copied() }
+
+    /// Keep [score] if it beats the previous result of the day
+    pub fn save_score(&self, score: DailyScore) {
+        let mut scores = Daily::scores();
+        let best = scores.entry(self.day).or_insert(score);
+        if score > *best { *best = score; }
+        saves::save(SCORES_PATH, &scores);
+    }
+}
+
+#[test]
+fn same_day_same_daily() {
+    let (d1, d2) = (Daily { day: 19000 }, Daily { day: 19000 });
+    assert_eq!(d1.seed(), d2.seed());
+    let counts = |d: Daily| d.bonus_items().into_iter().map(|(_, n)| n).collect::<Vec<_>>();
+    assert_eq!(counts(d1), counts(d2));
+    assert_ne!(d1.seed(), Daily { day: 19001 }.seed());
+}
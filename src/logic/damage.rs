@@ -6,8 +6,10 @@ use crate::entities::{Blink, MainShip, MuteShots, Ship, Shot};
 use crate::GameState;
 use crate::graphics::ScreenTransition;
 use crate::logic::{EliteKilled, ShipStatus, WaveCleared};
+use crate::logic::affix::{Affix, Affixes, EliteSplit};
+use crate::logic::boss::{BossPart, BossParts, PhaseTransition};
 use crate::logic::hit::HitEvent;
-use crate::logic::route::CurrentRoute;
+use crate::logic::route::{CurrentRoute, GameMode};
 use crate::music::{PlaySFXEvent, SFX};
 use crate::util::{in_states, space};
 
@@ -32,23 +34,39 @@ pub struct DamageEvent {
 }
 
 pub fn damage_ship(
+    mut commands: Commands,
     mut hit_events: EventReader<HitEvent>,
     mut ships: Query<(&mut Ship, Option<&MainShip>, Option<&Blink>)>,
     mut shots: Query<&Shot>,
+    invulnerable: Query<(), With<PhaseTransition>>,
+    mut bosses: Query<&mut BossParts>,
+    mut parts: Query<&mut BossPart>,
+    mut affixes: Query<&mut Affixes>,
     mut damage_event: EventWriter<DamageEvent>,
     mut ship_status: ResMut<ShipStatus>,
     mut sfx: EventWriter<PlaySFXEvent>,
 ) {
     let mut hit = None;
 
-    for HitEvent { ship, shot } in hit_events.iter() {
+    for HitEvent { ship, shot, part } in hit_events.iter() {
         if ships.contains(*ship) && shots.contains(*shot) {
             let (mut data, is_main_ship, is_blinking) = ships.get_mut(*ship).unwrap();
 
-            // Main ship invulnerable if blinking
-            if is_main_ship.and(is_blinking).is_none() {
+            // Main ship invulnerable if blinking, bosses while switching phases
+            if is_main_ship.and(is_blinking).is_none() && !invulnerable.contains(*ship) {
                 let mut damage = shots.get(*shot).unwrap().weapon.attack;
                 if is_main_ship.is_none() && ship_status.is_berserk() { damage *= 2.; }
+                // Parts take the hits instead of their boss, and may shield its core
+                if let Some(part) = part {
+                    let (Ok(mut boss), Ok(mut part_data)) = (bosses.get_mut(*ship), parts.get_mut(*part)) else { continue; };
+                    if !boss.vulnerable(&part_data) { continue; }
+                    hit = Some(false);
+                    if boss.damage(&mut part_data, damage) { commands.entity(*part).despawn_recursive(); }
+                    damage_event.send(DamageEvent { ship: *ship, fatal: false });
+                    continue;
+                }
+                if bosses.get(*ship).is_ok_and(|boss| boss.shields_core()) { continue; }
+                if let Ok(mut affixes) = affixes.get_mut(*ship) { damage = affixes.absorb(damage); }
                 if data.health > 0.001 {
                     if !data.model.is_shield() { hit = Some(data.friendly); }
                     if data.health < damage { data.health = 0.; }
@@ -95,6 +113,10 @@ pub fn elite_cleared(
     shots.for_each(|e| commands.entity(e).despawn_recursive());
 }
 
+/// Enemy whose death ends the fight, see [die_gracefully]
+#[derive(Component)]
+pub struct EndsFight;
+
 #[derive(Resource)]
 pub struct KillCount(pub usize);
 
@@ -103,18 +125,21 @@ pub fn die_gracefully(
     mut events: EventReader<DamageEvent>,
     mut time: ResMut<Time>,
     mut elite_killed: EventWriter<EliteKilled>,
-    ships: Query<(&Ship, Option<&MainShip>)>,
+    ships: Query<(&Ship, Option<&MainShip>, Option<&Affixes>, Option<&EliteSplit>)>,
+    splits: Query<&Ship, With<EliteSplit>>,
     mut sfx: EventWriter<PlaySFXEvent>,
     mut kill_count: Option<ResMut<KillCount>>,
 ) {
     let mut kills = 0;
+    let mut ended = false;
     for &DamageEvent { ship, fatal } in events.iter() {
         if !fatal { continue; }
         let entity = commands.get_entity(ship);
         if entity.is_some() {
-            let (ship, main) = ships.get(ship).unwrap();
+            let (ship, main, affixes, split) = ships.get(ship).unwrap();
             if ship.health < 0.001 {
-                entity.unwrap()
+                let mut entity = entity.unwrap();
+                entity
                     .insert(Dead)
                     .insert(MuteShots)
                 ;
@@ -123,7 +148,14 @@ pub fn die_gracefully(
                     sfx.send(PlaySFXEvent(SFX::Die));
                 }
                 else { kills += 1; }
-                if ship.model.is_elite() { elite_killed.send(EliteKilled); }
+                // The fight ends with the elite, or with the last invader it split into
+                let splitting = affixes.is_some_and(|affixes| affixes.has(Affix::Splitting));
+                let last_split = split.is_some() && splits.iter().all(|split| split.health < 0.001);
+                if !ended && ((ship.model.is_elite() && !splitting) || last_split) {
+                    ended = true;
+                    entity.insert(EndsFight);
+                    elite_killed.send(EliteKilled);
+                }
             }
         }
     }
@@ -135,12 +167,16 @@ pub fn despawn_ships(
     mut wave_cleared: EventWriter<WaveCleared>,
     mut route: ResMut<CurrentRoute>,
     mut transition: ResMut<ScreenTransition>,
-    dead: Query<(Entity, &Ship, Option<&MainShip>), (With<Dead>, Without<Blink>)>,
+    dead: Query<(Entity, Option<&MainShip>, Option<&EndsFight>), (With<Dead>, Without<Blink>)>,
 ) {
-    for (e, ship, main) in dead.iter() {
+    for (e, main, ends_fight) in dead.iter() {
         commands.entity(e).despawn_recursive();
-        if ship.model.is_elite() { wave_cleared.send(WaveCleared); }
+        if ends_fight.is_some() { wave_cleared.send(WaveCleared); }
         if main.is_some() {
+            if route.mode == GameMode::Practice {
+                transition.set_if_neq(ScreenTransition::to(GameState::Practice));
+                continue;
+            }
             route.lost = true;
             transition.set_if_neq(ScreenTransition::to(GameState::GameOver));
         }
@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use crate::entities::MainShip;
+use crate::logic::damage::DamageEvent;
+use crate::logic::ShipStatus;
+use crate::util::director;
+
+/// Adapts space waves to how well the player is doing, when switched on in the hangar in the
+/// [crate::logic::route::GameMode]s where [crate::logic::route::GameMode::adaptive] is true.
+///
+/// The director watches the hull, the recent damage, the kill speed and the credits of the player
+/// and sets an intensity which speeds up or slows down the waves, and picks enemies from the pool
+/// of the next or previous act.
+#[derive(Resource, Debug)]
+pub struct Director {
+    /// 1 is the regular difficulty, higher is harder
+    intensity: f32,
+    /// Damage taken recently, fades over [director::MEMORY] seconds
+    recent_damage: f32,
+    /// Enemies killed recently, fades over [director::MEMORY] seconds
+    recent_kills: f32,
+    last_hull: Option<f32>,
+    /// Last decision logged, (waits ratio in tenths, enemies act shift)
+    last_decision: (i32, isize),
+}
+
+impl Default for Director {
+    fn default() -> Self {
+        Director { intensity: 1., recent_damage: 0., recent_kills: 0., last_hull: None, last_decision: (10, 0) }
+    }
+}
+
+/// What the [Director] watches, since the previous observation
+pub struct Observation {
+    /// Current and max hull
+    pub hull: (f32, f32),
+    pub kills: usize,
+    pub credits: i16,
+    /// Seconds since the previous observation
+    pub dt: f32,
+}
+
+impl Director {
+    pub fn intensity(&self) -> f32 { self.intensity }
+
+    /// Speed at which the waits between spawns elapse
+    pub fn pace(&self) -> f32 { self.intensity }
+
+    /// Act of the enemy pool to draw from, [act] starting at 0
+    pub fn enemy_act(&self, act: usize) -> usize {
+        match self.enemies_shift() {
+            1 => act + 1,
+            -1 => act.saturating_sub(1),
+            _ => act,
+        }
+    }
+
+    fn enemies_shift(&self) -> isize {
+        if self.intensity >= director::STRONGER_ENEMIES { 1 }
+        else if self.intensity <= director::WEAKER_ENEMIES { -1 }
+        else { 0 }
+    }
+
+    fn target(&self, observation: &Observation) -> f32 {
+        let (hull, max_hull) = observation.hull;
+        let hull_ratio = if max_hull > 0. { hull / max_hull } else { 0. };
+        let kill_rate = self.recent_kills / director::MEMORY;
+        let wealth = observation.credits.clamp(0, director::RICH) as f32 / director::RICH as f32;
+        let target = 1.
+            + director::HULL_WEIGHT * (hull_ratio - 0.5)
+            - director::DAMAGE_WEIGHT * self.recent_damage
+            + director::KILL_WEIGHT * (kill_rate - director::KILL_RATE)
+            + director::CREDITS_WEIGHT * wealth;
+        target.clamp(director::MIN_INTENSITY, director::MAX_INTENSITY)
+    }
+
+    pub fn observe(&mut self, observation: Observation) {
+        let fade = (-observation.dt / director::MEMORY).exp();
+        let damage = self.last_hull.map_or(0., |last| (last - observation.hull.0).max(0.));
+        self.last_hull = Some(observation.hull.0);
+        self.recent_damage = self.recent_damage * fade + damage;
+        self.recent_kills = self.recent_kills * fade + observation.kills as f32;
+
+        let target = self.target(&observation);
+        let reaction = 1. - (-observation.dt / director::REACTION_TIME).exp();
+        self.intensity += (target - self.intensity) * reaction;
+
+        let decision = ((self.pace() * 10.).round() as i32, self.enemies_shift());
+        if decision != self.last_decision {
+            self.last_decision = decision;
+            info!(
+                "Director – intensity {:.2} (hull {:.0}/{:.0}, recent damage {:.1}, {:.2} kills/s, {} credits): waits x{:.1}, enemies of act {:+}",
+                self.intensity, observation.hull.0, observation.hull.1, self.recent_damage,
+                self.recent_kills / director::MEMORY, observation.credits, self.pace(), decision.1,
+            );
+        }
+    }
+
+    /// A new fight starts, the hull may have changed in between
+    pub fn new_fight(&mut self) { self.last_hull = None; }
+}
+
+pub fn observe(
+    time: Res<Time>,
+    status: Res<ShipStatus>,
+    mut director: ResMut<Director>,
+    mut damage: EventReader<DamageEvent>,
+    main_ship: Query<Entity, With<MainShip>>,
+) {
+    let kills = damage.iter()
+        .filter(|event| event.fatal && !main_ship.contains(event.ship))
+        .count();
+    director.observe(Observation {
+        hull: status.health(),
+        kills,
+        credits: status.get_credits(),
+        dt: time.delta_seconds(),
+    });
+}
+
+#[cfg(test)]
+fn play(director: &mut Director, seconds: usize, hull: (f32, f32), kills_every: usize, credits: i16) {
+    for s in 0..seconds {
+        let kills = if kills_every > 0 && s % kills_every == 0 { 1 } else { 0 };
+        director.observe(Observation { hull, kills, credits, dt: 1. });
+    }
+}
+
+#[test]
+fn director_eases_off_when_struggling() {
+    let mut director = Director::default();
+    play(&mut director, 10, (20., 20.), 0, 0);
+    let mut hull = 20.;
+    for _ in 0..30 {
+        hull -= 0.5;
+        play(&mut director, 1, (hull, 20.), 0, 0);
+    }
+    assert!(director.pace() < 1.);
+    assert_eq!(director.intensity(), director.intensity().clamp(director::MIN_INTENSITY, director::MAX_INTENSITY));
+    assert_eq!(director.enemy_act(1), 0);
+    assert_eq!(director.enemy_act(0), 0);
+}
+
+#[test]
+fn director_pushes_back_within_bounds() {
+    let mut director = Director::default();
+    play(&mut director, 300, (30., 30.), 2, 500);
+    assert!(director.pace() > 1.);
+    assert!(director.intensity() <= director::MAX_INTENSITY);
+    assert_eq!(director.enemy_act(1), 2);
+}
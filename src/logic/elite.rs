@@ -1,16 +1,36 @@
-use bevy::math::vec2;
-use rand::{Rng, thread_rng};
+use bevy::math::{Vec2, vec2};
+use rand::Rng;
 
-use crate::entities::Ships;
+use crate::entities::{Ships, Targeting, Weapons};
+use crate::logic::boss::{BossPhase, PartTemplate};
 use crate::logic::movement::Moves;
-use crate::logic::route::Route;
-use crate::logic::wave::SpecialEvent;
-use crate::util::{HALF_HEIGHT, HALF_WIDTH, HEIGHT};
+use crate::logic::rng::RunRng;
+use crate::logic::wave::{infinite_wave, Trigger, WaveEvent};
+use crate::util::{Angle, HALF_HEIGHT, HALF_WIDTH, HEIGHT};
 
-pub fn gen_elite_wave(_level: usize) -> Vec<SpecialEvent> {
-    match thread_rng().gen_range(0..4) {
+/// Track starting [events] once [trigger] is reached
+fn when(trigger: Trigger, events: Vec<WaveEvent>) -> Vec<WaveEvent> {
+    vec![WaveEvent::WaitFor(trigger), WaveEvent::Tracks(vec![events])]
+}
+
+/// Track spawning an enemy whenever fewer than 3 ships are left, and every 6 seconds anyway
+fn reinforcements(y: f32, right: bool) -> Vec<WaveEvent> {
+    vec![
+        WaveEvent::WaitFor(Trigger::Any(vec![Trigger::FewerThan(3), Trigger::After(6000)])),
+        WaveEvent::SpawnCrossing(y, right),
+        WaveEvent::Repeat,
+    ]
+}
+
+pub fn gen_elite_wave(_level: usize, rng: &mut RunRng) -> Vec<WaveEvent> {
+    elite_wave(rng.gen_range(0..4))
+}
+
+/// Elites call a second spawner when they get below half health
+pub fn elite_wave(variant: usize) -> Vec<WaveEvent> {
+    match variant {
         0 => vec![
-            SpecialEvent::Spawn(
+            WaveEvent::Spawn(
                 Ships::Elite(0),
                 Moves::DownUntil(
                     vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
@@ -18,10 +38,13 @@ pub fn gen_elite_wave(_level: usize) -> Vec<SpecialEvent> {
                     0.,
                     Box::new(Moves::Lemniscate(vec2(HALF_WIDTH, HALF_HEIGHT * 6. / 5.), 1.2, 32.)))
             ),
-            SpecialEvent::InfiniteWave(11000, HALF_HEIGHT - 4., true, 0.),
+            WaveEvent::Tracks(vec![
+                infinite_wave(11000, HALF_HEIGHT - 4., true),
+                when(Trigger::EliteBelow(0.5), infinite_wave(11000, HEIGHT as f32 - 24., false)),
+            ]),
         ],
         1 => vec![
-            SpecialEvent::Spawn(
+            WaveEvent::Spawn(
                 Ships::Elite(1),
                 Moves::DownUntil(
                     vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
@@ -29,10 +52,13 @@ pub fn gen_elite_wave(_level: usize) -> Vec<SpecialEvent> {
                     0.,
                     Box::new(Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 24.), 2.0, 32., 20.)))
             ),
-            SpecialEvent::InfiniteWave(8000, HEIGHT as f32 - 24., false, 0.),
+            WaveEvent::Tracks(vec![
+                infinite_wave(8000, HEIGHT as f32 - 24., false),
+                when(Trigger::EliteBelow(0.5), infinite_wave(8000, HALF_HEIGHT - 8., true)),
+            ]),
         ],
         2 => vec![
-            SpecialEvent::Spawn(
+            WaveEvent::Spawn(
                 Ships::Elite(2),
                 Moves::DownUntil(
                     vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
@@ -41,10 +67,13 @@ pub fn gen_elite_wave(_level: usize) -> Vec<SpecialEvent> {
                     Box::new(Moves::Astroid(vec2(HALF_WIDTH, HALF_HEIGHT + 24.), 1.2, 32., 12.))
                 )
             ),
-            SpecialEvent::InfiniteWave(10000, HEIGHT as f32 - 24., false, 0.),
+            WaveEvent::Tracks(vec![
+                infinite_wave(10000, HEIGHT as f32 - 24., false),
+                when(Trigger::EliteBelow(0.5), infinite_wave(10000, HALF_HEIGHT - 8., true)),
+            ]),
         ],
         _ => vec![
-            SpecialEvent::Spawn(
+            WaveEvent::Spawn(
                 Ships::Elite(3),
                 Moves::DownUntil(
                     vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
@@ -53,48 +82,179 @@ pub fn gen_elite_wave(_level: usize) -> Vec<SpecialEvent> {
                     Box::new(Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 20.), 0.8, 48., 16.))
                 )
             ),
-            SpecialEvent::InfiniteWave(10000, HEIGHT as f32 - 32., false, 0.),
+            WaveEvent::Tracks(vec![
+                infinite_wave(10000, HEIGHT as f32 - 32., false),
+                when(Trigger::EliteBelow(0.5), infinite_wave(10000, HALF_HEIGHT - 8., true)),
+            ]),
         ],
     }
 }
 
-pub fn gen_boss_wave(level: usize) -> Vec<SpecialEvent> {
-    match level / Route::act_len() {
+/// Bosses come back in the same order after act 3 in endless mode.
+/// Each phase starts its own adds, and the last boss also escalates with the enemies killed.
+pub fn gen_boss_wave(act: usize) -> Vec<WaveEvent> {
+    let n = (act % 3) as u8;
+    let phases = boss_phases(n);
+    let first = &phases[0].moves;
+    let start = *first.starting_pos();
+    let mut tracks = vec![];
+    for phase in phases.iter() {
+        if phase.from < 1. { tracks.push(when(Trigger::EliteBelow(phase.from), phase.adds.clone())); }
+        else { tracks.push(phase.adds.clone()); }
+    }
+    vec![
+        WaveEvent::Spawn(
+            Ships::Boss(n),
+            Moves::DownUntil(vec2(start.x, HEIGHT as f32 + 16.), start.y, 0., Box::new(first.clone()))
+        ),
+        WaveEvent::Tracks(tracks),
+    ]
+}
+
+/// Spread of [n] bullets [step] degrees apart, aimed down
+fn spread(n: usize, step: f32, y: f32) -> Vec<(Weapons, Vec2, Targeting)> {
+    (0..n)
+        .map(|i| i as f32 - (n - 1) as f32 / 2.)
+        .map(|i| (Weapons::Standard, vec2(i * 8., y), Targeting::Fixed(Angle(270. + i * step))))
+        .collect()
+}
+
+pub fn boss_phases(n: u8) -> Vec<BossPhase> {
+    match n {
         0 => vec![
-            SpecialEvent::Spawn(
-                Ships::Boss(0),
-                Moves::DownUntil(
-                    vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
-                    HALF_HEIGHT + 16.,
-                    0.,
-                    Box::new(Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 16.), 0.8, 0., 8.))
-                )
-            ),
-            SpecialEvent::InfiniteWave(4000, HEIGHT as f32 - 32., false, 0.),
+            BossPhase {
+                from: 1.,
+                moves: Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 16.), 0.8, 0., 8.),
+                weapons: spread(1, 0., -24.),
+                fire_rate: 0.5,
+                adds: infinite_wave(4000, HEIGHT as f32 - 32., false),
+            },
+            BossPhase {
+                from: 0.66,
+                moves: Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 24.), 0.8, 32., 8.),
+                weapons: spread(2, 20., -24.),
+                fire_rate: 1.,
+                adds: infinite_wave(6000, HALF_HEIGHT - 8., true),
+            },
+            BossPhase {
+                from: 0.33,
+                moves: Moves::Lemniscate(vec2(HALF_WIDTH, HALF_HEIGHT + 16.), 1.2, 32.),
+                weapons: [
+                    spread(3, 30., -24.),
+                    vec![(Weapons::Dual, vec2(0., -24.), Targeting::Sweep(Angle(225.), Angle(315.), 3.))],
+                ].concat(),
+                fire_rate: 1.25,
+                adds: reinforcements(HEIGHT as f32 - 56., false),
+            },
         ],
         1 => vec![
-            SpecialEvent::Spawn(
-                Ships::Boss(1),
-                Moves::DownUntil(
-                    vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
-                    HALF_HEIGHT + 40.,
-                    0.,
-                    Box::new(Moves::Astroid(vec2(HALF_WIDTH, HALF_HEIGHT + 40.), 1.0, 24., 16.))
-                )
-            ),
-            SpecialEvent::InfiniteWave(8000, HALF_HEIGHT - 8., true, 0.),
+            BossPhase {
+                from: 1.,
+                moves: Moves::Astroid(vec2(HALF_WIDTH, HALF_HEIGHT + 40.), 1.0, 24., 16.),
+                weapons: vec![
+                    (Weapons::Standard, vec2(-10., -24.), Targeting::Fixed(Angle(270.))),
+                    (Weapons::Standard, vec2(10., -24.), Targeting::Fixed(Angle(270.))),
+                ],
+                fire_rate: 1.,
+                adds: infinite_wave(8000, HALF_HEIGHT - 8., true),
+            },
+            BossPhase {
+                from: 0.66,
+                moves: Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 40.), 1.4, 40., 12.),
+                weapons: vec![
+                    (Weapons::Standard, vec2(-10., -24.), Targeting::Fixed(Angle(270.))),
+                    (Weapons::Dual, vec2(0., -24.), Targeting::Aimed),
+                    (Weapons::Standard, vec2(10., -24.), Targeting::Fixed(Angle(270.))),
+                ],
+                fire_rate: 1.,
+                adds: infinite_wave(8000, HEIGHT as f32 - 24., false),
+            },
+            BossPhase {
+                from: 0.33,
+                moves: Moves::Astroid(vec2(HALF_WIDTH, HALF_HEIGHT + 40.), 1.6, 40., 20.),
+                weapons: spread(4, 15., -24.),
+                fire_rate: 1.2,
+                adds: reinforcements(HALF_HEIGHT + 8., true),
+            },
         ],
         _ => vec![
-            SpecialEvent::Spawn(
-                Ships::Boss(2),
-                Moves::DownUntil(
-                    vec2(HALF_WIDTH, HEIGHT as f32 + 16.),
-                    HALF_HEIGHT + 16.,
-                    0.,
-                    Box::new(Moves::Lemniscate(vec2(HALF_WIDTH, HALF_HEIGHT + 16.), 1.0, 32.))
-                )
-            ),
-            SpecialEvent::InfiniteWave(10000, HEIGHT as f32 - 24., false, 0.),
+            BossPhase {
+                from: 1.,
+                moves: Moves::Lemniscate(vec2(HALF_WIDTH, HALF_HEIGHT + 16.), 1.0, 32.),
+                weapons: spread(5, 22.5, -24.),
+                fire_rate: 1.,
+                adds: vec![WaveEvent::Tracks(vec![
+                    infinite_wave(10000, HEIGHT as f32 - 24., false),
+                    when(Trigger::Kills(8), infinite_wave(8000, HALF_HEIGHT - 8., true)),
+                ])],
+            },
+            BossPhase {
+                from: 0.5,
+                moves: Moves::Ellipsis(vec2(HALF_WIDTH, HALF_HEIGHT + 24.), 1.0, 48., 16.),
+                weapons: [spread(5, 22.5, -24.), vec![(Weapons::Dual, vec2(0., -24.), Targeting::Leading)]].concat(),
+                fire_rate: 1.15,
+                adds: reinforcements(HEIGHT as f32 - 40., false),
+            },
+            BossPhase {
+                from: 0.2,
+                moves: Moves::Lemniscate(vec2(HALF_WIDTH, HALF_HEIGHT + 8.), 1.6, 40.),
+                weapons: [spread(7, 15., -24.), vec![(Weapons::Beam(800), vec2(0., -24.), Targeting::Aimed)]].concat(),
+                fire_rate: 1.3,
+                adds: vec![],
+            },
+        ],
+    }
+}
+
+/// Turrets and wings on top of the bosses, some of them shield the core
+pub fn boss_parts(n: u8) -> Vec<PartTemplate> {
+    let turret = |offset: Vec2, after: Vec<usize>, shields_core: bool| PartTemplate {
+        offset,
+        hitbox: vec2(8., 8.),
+        sprite: 4,
+        health: 0.1,
+        weapons: vec![(Weapons::Standard, vec2(0., -4.), Targeting::Fixed(Angle(270.)))],
+        after,
+        shields_core,
+    };
+    match n {
+        0 => vec![
+            turret(vec2(-16., -12.), vec![], true),
+            turret(vec2(16., -12.), vec![], true),
+        ],
+        1 => vec![
+            PartTemplate {
+                offset: vec2(-24., 0.),
+                hitbox: vec2(12., 12.),
+                sprite: 13,
+                health: 0.15,
+                weapons: vec![(Weapons::Standard, vec2(0., -6.), Targeting::Fixed(Angle(250.)))],
+                after: vec![],
+                shields_core: true,
+            },
+            PartTemplate {
+                offset: vec2(24., 0.),
+                hitbox: vec2(12., 12.),
+                sprite: 13,
+                health: 0.15,
+                weapons: vec![(Weapons::Standard, vec2(0., -6.), Targeting::Fixed(Angle(290.)))],
+                after: vec![],
+                shields_core: true,
+            },
+            PartTemplate {
+                offset: vec2(0., -16.),
+                hitbox: vec2(8., 8.),
+                sprite: 8,
+                health: 0.1,
+                weapons: vec![(Weapons::Dual, vec2(0., -4.), Targeting::Aimed)],
+                after: vec![0, 1],
+                shields_core: false,
+            },
+        ],
+        _ => vec![
+            turret(vec2(-16., -16.), vec![], true),
+            turret(vec2(16., -16.), vec![], true),
+            turret(vec2(0., -20.), vec![0, 1], false),
         ],
     }
-}
\ No newline at end of file
+}
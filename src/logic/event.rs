@@ -0,0 +1,113 @@
+use bevy::reflect::{TypePath, TypeUuid};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::logic::{Items, ShipStatus};
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
+use crate::logic::upgrades::Upgrades;
+
+/// Consequence of an event choice
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum Effect {
+    /// Repair (or damage if negative) the hull, the ship never goes below 1 HP
+    Hull(i16),
+    /// Gain (or pay if negative) credits, the choice can't be picked without enough credits
+    Credits(i16),
+    Missiles(usize),
+    Shields(usize),
+    /// Random upgrade, like the ones sold in the shop
+    Upgrade,
+    /// Skip the next node, unless it is a boss or a choice would be lost, see [CurrentRoute::skip]
+    SkipNode,
+    AngryShopkeepers(bool),
+}
+
+impl Effect {
+    pub fn text(&self) -> String {
+        match *self {
+            Effect::Hull(n) => format!("{:+} hull", n),
+            Effect::Credits(n) => format!("{:+} credits", n),
+            Effect::Missiles(n) => format!("+{} missiles", n),
+            Effect::Shields(n) => format!("+{} shields", n),
+            Effect::Upgrade => "Upgrade!".to_string(),
+            Effect::SkipNode => "Shortcut!".to_string(),
+            Effect::AngryShopkeepers(true) => "The shopkeepers are angry".to_string(),
+            Effect::AngryShopkeepers(false) => "The shopkeepers calm down".to_string(),
+        }
+    }
+
+    pub fn apply(&self, status: &mut ShipStatus, route: &mut CurrentRoute, rng: &mut RunRng) {
+        match *self {
+            Effect::Hull(n) => {
+                let (health, max_health) = status.health();
+                status.set_health((health + n as f32).clamp(1., max_health));
+            }
+            Effect::Credits(n) => status.add_credits(n),
+            Effect::Missiles(n) => for _ in 0..n { status.add(&Items::Missile) },
+            Effect::Shields(n) => for _ in 0..n { status.add(&Items::Shield) },
+            Effect::Upgrade => {
+                let upgrade = Upgrades::new_upgrade(status, rng);
+                status.add(&Items::Upgrade(upgrade));
+            }
+            Effect::SkipNode => route.skip(rng),
+            Effect::AngryShopkeepers(angry) => route.set_angry_shopkeepers(angry),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EventChoice {
+    pub text: String,
+    /// Text shown after picking the choice
+    pub result: String,
+    pub effects: Vec<Effect>,
+}
+
+impl EventChoice {
+    pub fn affordable(&self, status: &ShipStatus) -> bool {
+        let cost: i16 = self.effects.iter().map(|e| match e {
+            Effect::Credits(n) if *n < 0 => -n,
+            _ => 0,
+        }).sum();
+        cost <= status.get_credits()
+    }
+}
+
+/// Scenario shown by a [crate::logic::route::Level::Event] node
+#[derive(Clone, Debug, Deserialize)]
+pub struct RouteEvent {
+    pub text: String,
+    pub choices: Vec<EventChoice>,
+}
+
+/// Events of `assets/data/route.events.ron`
+#[derive(Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "b0c6e2f1-7a39-4d5e-8c14-6e2b9f0d3a71"]
+#[serde(try_from = "Vec<RouteEvent>")]
+pub struct RouteEvents(Vec<RouteEvent>);
+
+impl TryFrom<Vec<RouteEvent>> for RouteEvents {
+    type Error = String;
+
+    fn try_from(events: Vec<RouteEvent>) -> Result<Self, Self::Error> {
+        if events.is_empty() { return Err("at least 1 event is needed".to_string()); }
+        if let Some(event) = events.iter().find(|e| !(2..=3).contains(&e.choices.len())) {
+            return Err(format!("event \"{}\" must have 2 or 3 choices", event.text.lines().next().unwrap_or_default()));
+        }
+        Ok(RouteEvents(events))
+    }
+}
+
+impl RouteEvents {
+    pub fn random(&self, rng: &mut RunRng) -> &RouteEvent {
+        &self.0[rng.gen_range(0..self.0.len())]
+    }
+}
+
+#[test]
+fn events_need_2_or_3_choices() {
+    assert!(ron::from_str::<RouteEvents>(include_str!("../../assets/data/route.events.ron")).is_ok());
+    let one_choice = r#"[(text: "Derelict", choices: [(text: "Leave", result: "You leave.", effects: [])])]"#;
+    assert!(ron::from_str::<RouteEvents>(one_choice).unwrap_err().to_string().contains("2 or 3 choices"));
+}
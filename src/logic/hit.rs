@@ -22,6 +22,8 @@ impl Plugin for HitProcessingPlugin {
 pub struct HitEvent {
     pub shot: Entity,
     pub ship: Entity,
+    /// Part of the boss [HitEvent::ship] hit by the shot
+    pub part: Option<Entity>,
 }
 
 pub fn clear_shots(
@@ -1,8 +1,10 @@
-use bevy::prelude::{Commands, Res, Resource};
+use bevy::prelude::{Assets, Commands, Res, Resource};
 use bevy::utils::HashMap;
-use rand::{RngCore, thread_rng};
+use rand::RngCore;
 
-use crate::entities::Ship;
+use crate::entities::ShipRegistry;
+use crate::logic::daily::Daily;
+use crate::logic::rng::RunRng;
 use crate::logic::route::CurrentRoute;
 use crate::logic::upgrades::{BOUNCING, PIERCING, STUN, Upgrades};
 use crate::screens;
@@ -18,6 +20,10 @@ pub struct ShipStatus {
 }
 
 impl ShipStatus {
+    pub fn new(max_health: f32, inventory: HashMap<Items, usize>) -> Self {
+        ShipStatus { inventory, upgrades: vec![], health: max_health, max_health, credits: items::STARTING_CREDITS }
+    }
+
     pub fn add(&mut self, item: &Items) {
         if *item == Items::Repair {
             if self.health < self.max_health { self.health += 1.; }
@@ -118,15 +124,16 @@ pub fn reset_inventory(
     mut commands: Commands,
     route: Res<CurrentRoute>,
     selected_ship: Res<screens::SelectedShip>,
+    daily: Option<Res<Daily>>,
+    data: Res<screens::Data>,
+    ships: Res<Assets<ShipRegistry>>,
 ) {
-    let ship = Ship::from(selected_ship.0.model());
-    commands.insert_resource(ShipStatus {
-        inventory: items::STARTING_ITEMS[&route.mode].clone().into_iter().collect(),
-        upgrades: vec![],
-        health: ship.max_health,
-        max_health: ship.max_health,
-        credits: items::STARTING_CREDITS,
-    });
+    let ship = ships.get(&data.ships).unwrap().ship(selected_ship.0.model());
+    let mut inventory: HashMap<Items, usize> = items::STARTING_ITEMS[&route.mode].clone().into_iter().collect();
+    if let Some(daily) = daily {
+        for (item, n) in daily.bonus_items() { *inventory.entry(item).or_insert(0) += n; }
+    }
+    commands.insert_resource(ShipStatus::new(ship.max_health, inventory));
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
@@ -147,8 +154,7 @@ impl Items {
         }
     }
 
-    pub fn random_collectible() -> Self {
-        let mut rng = thread_rng();
+    pub fn random_collectible(rng: &mut RunRng) -> Self {
         if rng.next_u32() % 2 == 0 { Items::Missile } else { Items::Shield }
     }
 }
\ No newline at end of file
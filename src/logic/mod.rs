@@ -1,12 +1,15 @@
 use bevy::app::App;
 use bevy::prelude::*;
 
+pub use boss::BossPhases;
 pub use item::Items;
 pub use item::ShipStatus;
 pub use loot::Loot;
+pub use movement::Formation;
 pub use wave::EliteKilled;
 pub use wave::ShipBundle;
 pub use wave::WaveCleared;
+pub use wave::WaveTemplates;
 
 use crate::GameState;
 use crate::logic::damage::DamagePlugin;
@@ -19,22 +22,40 @@ pub mod upgrades;
 pub mod hit;
 pub mod damage;
 pub mod route;
+pub mod rng;
+pub mod event;
+pub mod daily;
+pub mod director;
+pub mod practice;
+pub mod timeline;
 mod wave;
 mod movement;
 mod loot;
 mod item;
 mod elite;
+pub mod boss;
+pub mod affix;
+pub mod progress;
+mod saves;
 
 pub struct LogicPlugin;
 
 impl Plugin for LogicPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<practice::Practice>()
             .add_systems(OnExit(GameState::Hangar), item::reset_inventory)
             .add_systems(Update, (upgrades::bounce_shots, upgrades::leech, upgrades::unmute)
                 .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss]))
             )
-            .add_systems(Update, movement::apply_movement)
+            .add_systems(Update, (movement::apply_movement, movement::update_formations).chain())
+            .add_systems(Update, director::observe
+                .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss]).and_then(resource_exists::<director::Director>()))
+            )
+            .add_systems(Update, ((boss::spawn_parts, boss::update_phases).chain(), boss::end_transitions).run_if(in_state(GameState::Boss)))
+            .add_systems(Update, (affix::spawn_barriers, affix::update_affixes.after(movement::apply_movement), affix::enrage, affix::split_elites)
+                .run_if(in_state(GameState::Elite))
+            )
             .add_systems(PostUpdate, movement::despawn_far_ships)
             .add_plugins((HitProcessingPlugin, DamagePlugin, WavePlugin, LootPlugin))
         ;
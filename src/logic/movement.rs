@@ -3,14 +3,20 @@ use std::ops::Add;
 
 use bevy::hierarchy::DespawnRecursiveExt;
 use bevy::math::{Vec2, vec2, Vec3Swizzles};
-use bevy::prelude::{Commands, Component, Entity, Query, Res, Time, Transform, Without};
-use rand::{Rng, RngCore, thread_rng};
+use bevy::prelude::{AnyOf, Commands, Component, Entity, Query, Res, Time, Transform, Without};
+use bevy::utils::HashMap;
+use rand::{Rng, RngCore};
+use serde::Deserialize;
 
-use crate::entities::{MainShip, Ship};
+use crate::entities::{MainShip, Ship, ShipRegistry, Ships};
 use crate::graphics::FakeTransform;
+use crate::logic::affix::EliteSplit;
+use crate::logic::boss::BossPart;
+use crate::logic::damage::Dead;
+use crate::logic::rng::RunRng;
 use crate::util::{Angle, HALF_HEIGHT, HALF_WIDTH, HEIGHT, WIDTH};
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Moves {
     Linear(Vec2, Angle),
     /// starting, angle, frequency, amplitude
@@ -29,16 +35,17 @@ pub enum Moves {
     Astroid(Vec2, f32, f32, f32),
     /// starting, final y, t_y, original move
     DownUntil(Vec2, f32, f32, Box<Moves>),
+    /// starting, target, t_target, original move starting at the target
+    MoveTo(Vec2, Vec2, f32, Box<Moves>),
 }
 
 impl Moves {
-    pub fn random_crossing(y: f32) -> Self {
-        let mut rng = thread_rng();
-        Self::random_crossing_dir(y, rng.next_u32() % 2 == 0)
+    pub fn random_crossing(y: f32, rng: &mut RunRng) -> Self {
+        let right = rng.next_u32() % 2 == 0;
+        Self::random_crossing_dir(y, right, rng)
     }
 
-    pub fn random_crossing_dir(y: f32, right: bool) -> Self {
-        let mut rng = thread_rng();
+    pub fn random_crossing_dir(y: f32, right: bool, rng: &mut RunRng) -> Self {
         let (pos, angle) = if right {
             // Left to right
             (vec2(-16., y), 0.)
@@ -65,7 +72,8 @@ impl Moves {
             | Moves::Ellipsis(pos, _, _, _)
             | Moves::Lemniscate(pos, _, _)
             | Moves::Astroid(pos, _, _, _)
-            | Moves::DownUntil(pos, _, _, _) => pos,
+            | Moves::DownUntil(pos, _, _, _)
+            | Moves::MoveTo(pos, _, _, _) => pos,
             Moves::WithPause(_, _, _, moves)
             | Moves::StationaryAt(_, _, moves) =>
                 moves.starting_pos(),
@@ -143,6 +151,19 @@ impl Moves {
                     new_pos
                 }
             }
+            Moves::MoveTo(starting, target, t_target, original) => {
+                if *t_target > 0. {
+                    original.pos(time - *t_target, delta, speed)
+                } else {
+                    let distance = starting.distance(*target);
+                    if time * speed >= distance {
+                        *t_target = time.max(f32::EPSILON);
+                        *target
+                    } else {
+                        starting.lerp(*target, time * speed / distance)
+                    }
+                }
+            }
         }
     }
 }
@@ -160,21 +181,157 @@ pub struct Movement {
     pub(crate) t_0: f32,
 }
 
+/// Shape of a [Formation], given for ships flying to the right
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub enum FormationShape {
+    /// Leader on the anchor, the others alternating on both sides behind it (spacing)
+    V(f32),
+    /// Vertical line (spacing)
+    Line(f32),
+    /// Rows of ships from front to back (columns, spacing)
+    Grid(usize, f32),
+}
+
+impl FormationShape {
+    /// Offset from the anchor of the [i]-th of [n] ships
+    pub fn offset(&self, i: usize, n: usize) -> Vec2 {
+        match *self {
+            FormationShape::V(spacing) => {
+                let rank = ((i + 1) / 2) as f32;
+                let side = if i % 2 == 0 { 1. } else { -1. };
+                vec2(-rank * spacing, side * rank * spacing)
+            }
+            FormationShape::Line(spacing) => vec2(0., ((n - 1) as f32 / 2. - i as f32) * spacing),
+            FormationShape::Grid(columns, spacing) => {
+                let rows = n.div_ceil(columns);
+                let (row, column) = (i / columns, i % columns);
+                let columns = columns.min(n);
+                vec2(
+                    ((columns - 1) as f32 / 2. - column as f32) * spacing,
+                    ((rows - 1) as f32 / 2. - row as f32) * spacing,
+                )
+            }
+        }
+    }
+}
+
+/// Invisible anchor moved by its [Movement], followed by the [FormationMember]s
+#[derive(Component)]
+pub struct Formation {
+    pub shape: FormationShape,
+    /// Speed of the slowest member
+    pub speed: f32,
+    /// Horizontal direction of the anchor, the shape is mirrored when flying to the left
+    right: bool,
+    last_pos: Vec2,
+    /// Members alive, to notice when one dies
+    members: usize,
+    /// Seconds left before the survivors reform after a member died
+    scatter: f32,
+}
+
+impl Formation {
+    pub fn new(shape: FormationShape, models: &[Ships], moves: &Moves, ships: &ShipRegistry) -> Self {
+        let speed = models.iter().map(|&model| ships.ship(model).speed).fold(f32::MAX, f32::min);
+        let start = *moves.starting_pos();
+        Formation { shape, speed, right: start.x < HALF_WIDTH, last_pos: start, members: models.len(), scatter: 0. }
+    }
+
+    /// Offset from the anchor of the [slot]-th of [n] ships
+    pub fn offset(&self, slot: usize, n: usize) -> Vec2 {
+        let offset = self.shape.offset(slot, n);
+        if self.right { offset } else { vec2(-offset.x, offset.y) }
+    }
+}
+
+/// Ship holding the [slot]-th place of a [Formation]
+#[derive(Component)]
+pub struct FormationMember {
+    pub anchor: Entity,
+    pub slot: usize,
+}
+
+/// How fast members join their slot when the formation reforms
+const FORMATION_REFORM_RATE: f32 = 3.;
+/// Seconds during which the survivors break away from the anchor when a member dies
+const FORMATION_SCATTER_DURATION: f32 = 0.6;
+/// Pixels per second
+const FORMATION_SCATTER_SPEED: f32 = 24.;
+
 pub fn apply_movement(
     time: Res<Time>,
-    mut query: Query<(&mut Movement, &Ship, &mut FakeTransform)>,
+    mut query: Query<(&mut Movement, AnyOf<(&Ship, &Formation)>, &mut FakeTransform)>,
 ) {
-    for (mut movement, ship, mut pos) in query.iter_mut() {
+    for (mut movement, (ship, formation), mut pos) in query.iter_mut() {
         let t_0 = movement.t_0;
-        let new_pos = movement.moves.pos(time.elapsed_seconds() - t_0, time.delta_seconds(), ship.speed);
+        let speed = match (ship, formation) {
+            (Some(ship), _) => ship.speed,
+            (_, Some(formation)) => formation.speed,
+            _ => continue,
+        };
+        let new_pos = movement.moves.pos(time.elapsed_seconds() - t_0, time.delta_seconds(), speed);
         pos.translation.x = new_pos.x;
         pos.translation.y = new_pos.y;
     }
 }
 
+/// Members follow their anchor. When a member dies the formation breaks up: the survivors fly
+/// away from the anchor for a moment, then take the first slots of the shape and glide to them.
+/// The anchor goes away with its last member.
+pub fn update_formations(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut anchors: Query<(Entity, &mut Formation, &FakeTransform)>,
+    mut members: Query<(Entity, &mut FormationMember, &mut FakeTransform), (Without<Dead>, Without<Formation>)>,
+) {
+    let mut groups: HashMap<Entity, Vec<(usize, Entity)>> = HashMap::new();
+    for (e, member, _) in members.iter() {
+        groups.entry(member.anchor).or_default().push((member.slot, e));
+    }
+
+    let blend = 1. - (-FORMATION_REFORM_RATE * time.delta_seconds()).exp();
+    for (anchor, mut formation, anchor_pos) in anchors.iter_mut() {
+        let Some(mut group) = groups.remove(&anchor) else {
+            commands.entity(anchor).despawn_recursive();
+            continue;
+        };
+
+        let anchor_pos = anchor_pos.translation.xy();
+        let delta = anchor_pos - formation.last_pos;
+        if delta.x.abs() > 0.01 { formation.right = delta.x > 0.; }
+        formation.last_pos = anchor_pos;
+
+        let n = group.len();
+        if n < formation.members { formation.scatter = FORMATION_SCATTER_DURATION; }
+        formation.members = n;
+        if formation.scatter > 0. {
+            formation.scatter -= time.delta_seconds();
+            for (_, e) in group {
+                let Ok((_, _, mut pos)) = members.get_mut(e) else { continue; };
+                let away = (pos.translation.xy() - anchor_pos).normalize_or_zero();
+                let new_pos = pos.translation.xy() + delta + away * FORMATION_SCATTER_SPEED * time.delta_seconds();
+                pos.translation.x = new_pos.x;
+                pos.translation.y = new_pos.y;
+            }
+            continue;
+        }
+
+        group.sort();
+        for (slot, (_, e)) in group.into_iter().enumerate() {
+            let Ok((_, mut member, mut pos)) = members.get_mut(e) else { continue; };
+            member.slot = slot;
+            let target = anchor_pos + formation.offset(slot, n);
+            let new_pos = (pos.translation.xy() + delta).lerp(target, blend);
+            pos.translation.x = new_pos.x;
+            pos.translation.y = new_pos.y;
+        }
+    }
+}
+
+/// Elite splits are kept, the fight only ends once they are killed
 pub fn despawn_far_ships(
     mut commands: Commands,
-    ships: Query<(Entity, &Transform), Without<MainShip>>,
+    ships: Query<(Entity, &Transform), (Without<MainShip>, Without<BossPart>, Without<EliteSplit>)>,
 ) {
     let center = vec2(HALF_WIDTH, HALF_HEIGHT);
     for (e, pos) in ships.iter() {
@@ -183,3 +340,15 @@ pub fn despawn_far_ships(
         }
     }
 }
+
+#[test]
+fn formation_slots_dont_overlap() {
+    for shape in [FormationShape::V(12.), FormationShape::Line(12.), FormationShape::Grid(3, 12.)] {
+        for n in 1..8 {
+            let offsets: Vec<Vec2> = (0..n).map(|i| shape.offset(i, n)).collect();
+            for (i, a) in offsets.iter().enumerate() {
+                assert!(offsets[i + 1..].iter().all(|b| a.distance(*b) >= 12.));
+            }
+        }
+    }
+}
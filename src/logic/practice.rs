@@ -0,0 +1,93 @@
+use bevy::prelude::Resource;
+use bevy::utils::HashMap;
+
+use crate::entities::Ship;
+use crate::GameState;
+use crate::logic::{Items, ShipStatus};
+use crate::logic::upgrades::Upgrades;
+
+/// Fight picked from the practice screen
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PracticeFight {
+    /// Regular fight with the difficulty of the given act (starting at 0)
+    Space(usize),
+    /// Variant of [crate::logic::elite::elite_wave]
+    Elite(usize),
+    /// Boss of the given act (starting at 0)
+    Boss(usize),
+}
+
+impl PracticeFight {
+    pub const ALL: [PracticeFight; 10] = [
+        PracticeFight::Space(0), PracticeFight::Space(1), PracticeFight::Space(2),
+        PracticeFight::Elite(0), PracticeFight::Elite(1), PracticeFight::Elite(2), PracticeFight::Elite(3),
+        PracticeFight::Boss(0), PracticeFight::Boss(1), PracticeFight::Boss(2),
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            PracticeFight::Space(act) => format!("Fight (act {})", act + 1),
+            PracticeFight::Elite(n) => format!("Elite {}", n + 1),
+            PracticeFight::Boss(n) => format!("Boss {}", n + 1),
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        match self {
+            PracticeFight::Space(_) => GameState::Space,
+            PracticeFight::Elite(_) => GameState::Elite,
+            PracticeFight::Boss(_) => GameState::Boss,
+        }
+    }
+
+    /// Column of the route the fight is played at, which sets its difficulty
+    pub fn level(&self, act_len: usize) -> usize {
+        match self {
+            PracticeFight::Space(act) => act * act_len,
+            PracticeFight::Elite(_) => act_len,
+            PracticeFight::Boss(act) => act * act_len + act_len - 1,
+        }
+    }
+}
+
+/// Fight and loadout picked from the practice screen, kept between practice sessions
+#[derive(Resource)]
+pub struct Practice {
+    pub fight: usize,
+    pub hull: usize,
+    pub missiles: usize,
+    pub shields: usize,
+    /// Number of times each upgrade is picked
+    pub upgrades: Vec<(Upgrades, usize)>,
+}
+
+impl Default for Practice {
+    fn default() -> Self {
+        Self {
+            fight: 0,
+            hull: 20,
+            missiles: 2,
+            shields: 2,
+            upgrades: [
+                Upgrades::Speed, Upgrades::Damage, Upgrades::ShotSpeed, Upgrades::ShotFrequency, Upgrades::Hull,
+                Upgrades::BouncingShots, Upgrades::PiercingShots, Upgrades::LeechShots, Upgrades::StunShots,
+                Upgrades::SideShots, Upgrades::Berserk, Upgrades::BetterShields, Upgrades::BetterMissiles,
+            ].into_iter().map(|u| (u, 0)).collect(),
+        }
+    }
+}
+
+impl Practice {
+    pub fn fight(&self) -> PracticeFight { PracticeFight::ALL[self.fight] }
+
+    pub fn ship_status(&self, ship: &Ship) -> ShipStatus {
+        let inventory = HashMap::from([(Items::Missile, self.missiles), (Items::Shield, self.shields)]);
+        let mut status = ShipStatus::new(ship.max_health, inventory);
+        for &(upgrade, n) in self.upgrades.iter() {
+            for _ in 0..n { status.add(&Items::Upgrade(upgrade)); }
+        }
+        let max_health = status.health().1;
+        status.set_health((self.hull as f32).min(max_health));
+        status
+    }
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::Resource;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::WeaponModule;
+use crate::logic::saves;
+
+/// Save file keeping what was unlocked across runs
+const PROGRESS_PATH: &str = "progress.ron";
+
+/// Best results over every run, unlocking weapon modules
+#[derive(Resource, Copy, Clone, Default, Deserialize, Serialize)]
+pub struct Progress {
+    /// Most acts cleared in a single run
+    pub acts_cleared: usize,
+}
+
+impl Progress {
+    pub fn load() -> Self {
+        saves::load(PROGRESS_PATH).unwrap_or_default()
+    }
+
+    /// Keep [acts] if it beats the previous best, returns true if new modules may be unlocked
+    pub fn record(&mut self, acts: usize) -> bool {
+        if acts <= self.acts_cleared { return false; }
+        self.acts_cleared = acts;
+        saves::save(PROGRESS_PATH, self);
+        true
+    }
+
+    pub fn unlocked(&self, module: &WeaponModule) -> bool { module.unlock <= self.acts_cleared }
+
+    /// Index of the unlocked module [step] modules after [current], wrapping around
+    pub fn cycle(&self, modules: &[WeaponModule], current: usize, step: isize) -> usize {
+        let n = modules.len() as isize;
+        let mut index = current as isize;
+        for _ in 0..n {
+            index = (index + step).rem_euclid(n);
+            if self.unlocked(&modules[index as usize]) { return index as usize; }
+        }
+        current
+    }
+}
+
+#[test]
+fn modules_unlock_with_progress() {
+    let modules = crate::entities::registry().modules().to_vec();
+    let fresh = Progress::default();
+    assert_eq!(fresh.cycle(&modules, 3, 1), 0);
+    assert_eq!(fresh.cycle(&modules, 0, -1), 3);
+
+    let veteran = Progress { acts_cleared: 3 };
+    assert_eq!(veteran.cycle(&modules, 0, -1), modules.len() - 1);
+    assert!(modules.iter().all(|module| veteran.unlocked(module)));
+}
@@ -0,0 +1,50 @@
+use bevy::prelude::Resource;
+use rand::{Error, RngCore, SeedableRng, thread_rng};
+use rand::rngs::StdRng;
+
+/// Run-scoped random number generator.
+///
+/// Every random decision of a run (route, waves, elites, upgrades, shop…) draws from it,
+/// so that the same seed, ship and [crate::logic::route::GameMode] always give the same run.
+#[derive(Resource)]
+pub struct RunRng {
+    seed: u64,
+    /// Level the generator is currently seeded for, [None] while generating the route
+    level: Option<usize>,
+    rng: StdRng,
+}
+
+impl RunRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, level: None, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    /// Seeds are kept short enough to be typed in from the hangar
+    pub fn random_seed() -> u64 { thread_rng().next_u32() as u64 }
+
+    pub fn seed(&self) -> u64 { self.seed }
+
+    /// Reseed the generator for [level] so that a level always draws the same values,
+    /// whatever happened in the previous ones.
+    pub fn at_level(&mut self, level: usize) -> &mut Self {
+        if self.level != Some(level) {
+            self.level = Some(level);
+            self.rng = StdRng::seed_from_u64(self.seed ^ (level as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        self
+    }
+
+    /// Reseed the generator for the generation of [act], used when the route grows during the run.
+    pub fn at_act(&mut self, act: usize) -> &mut Self {
+        self.level = None;
+        self.rng = StdRng::seed_from_u64(!self.seed ^ (act as u64 + 1).wrapping_mul(0xC2B2_AE3D_27D4_EB4F));
+        self
+    }
+}
+
+impl RngCore for RunRng {
+    fn next_u32(&mut self) -> u32 { self.rng.next_u32() }
+    fn next_u64(&mut self) -> u64 { self.rng.next_u64() }
+    fn fill_bytes(&mut self, dest: &mut [u8]) { self.rng.fill_bytes(dest) }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> { self.rng.try_fill_bytes(dest) }
+}
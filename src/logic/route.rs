@@ -1,9 +1,15 @@
+use std::collections::HashMap;
+
 use bevy::prelude::Resource;
-use rand::{Rng, RngCore, thread_rng};
+use bevy::reflect::{TypePath, TypeUuid};
+use rand::RngCore;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
 
 use crate::GameState;
+use crate::logic::rng::RunRng;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
 pub enum Level {
     /// Regular fight, see [crate::logic::wave]
     Space,
@@ -18,6 +24,10 @@ pub enum Level {
     Repair,
     /// Fight with a strong enemy, loots a good amount of credits and some bombs / shields
     Elite,
+    /// Short scenario with a few choices, see [crate::logic::event]
+    Event,
+    /// Bet credits on slot reels or hidden crates
+    Casino,
     /// Can be anything except [Level::Boss]
     Unknown,
 
@@ -33,11 +43,30 @@ impl Level {
             Level::Shop => "Shop",
             Level::Repair => "Repair station",
             Level::Elite => "Elite",
+            Level::Event => "Event",
+            Level::Casino => "Casino",
             Level::Unknown => "???",
             Level::Win => "You won!",
         }
     }
 
+    /// Name short enough to fit in the route overview
+    pub fn short_name(&self) -> &str {
+        match self {
+            Level::Space => "Fight",
+            Level::Upgrade => "Upgrade",
+            Level::Boss => "Boss",
+            Level::Shop => "Shop",
+            Level::Repair => "Repair",
+            Level::Elite => "Elite",
+            Level::Event => "Event",
+            Level::Casino => "Casino",
+            Level::Unknown => "???",
+            Level::Win => "Win",
+        }
+    }
+
+    /// [Level::Unknown] must be resolved with [RouteTemplate::unknown_level] first
     pub fn state(&self) -> GameState {
         match self {
             Level::Space => GameState::Space,
@@ -46,108 +75,139 @@ impl Level {
             Level::Shop => GameState::Shop,
             Level::Upgrade => GameState::Upgrade,
             Level::Repair => GameState::Repair,
-            Level::Unknown => Level::unknown().state(),
+            Level::Event => GameState::Event,
+            Level::Casino => GameState::Casino,
+            Level::Unknown => unreachable!("Unknown levels are resolved by the route template"),
             Level::Win => GameState::GameOver,
         }
     }
+}
 
-    fn random() -> Self {
-        let mut rng = thread_rng();
-        let options = [
-            Level::Unknown,
-            Level::Unknown,
-            Level::Unknown,
-            Level::Unknown,
-            Level::Unknown,
-            Level::Shop,
-            Level::Shop,
-            Level::Shop,
-            Level::Shop,
-            Level::Repair,
-            Level::Repair,
-            Level::Elite,
-            Level::Elite,
-            Level::Upgrade,
-        ];
-        return options[rng.gen_range(0..options.len())]
-    }
-
-    fn unknown() -> Self {
-        let mut rng = thread_rng();
-        let options = [
-            Level::Space,
-            Level::Space,
-            Level::Space,
-            Level::Elite,
-            Level::Shop,
-            Level::Shop,
-            Level::Repair,
-            Level::Repair,
-            Level::Upgrade,
-        ];
-        return options[rng.gen_range(0..options.len())]
-    }
-}
-
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-pub enum RouteElement {
+/// Generation rule for a column of the route
+#[derive(Copy, Clone, Debug, Deserialize)]
+enum Slot {
+    /// A single node that every path goes through
+    Single(Level),
+    /// One node per parent, neighbours may merge
     Level(Level),
-    Choice(Level, Level),
+    /// Two different random levels per parent
+    Choice,
+    /// Two different levels per parent, one of them being the given level
+    ChoiceWith(Level),
 }
 
-impl RouteElement {
-    pub fn state(&self) -> Option<GameState> {
+impl Slot {
+    fn children(&self) -> usize {
         match self {
-            RouteElement::Level(l) => Some(l.state()),
-            RouteElement::Choice(_, _) => None,
+            Slot::Single(_) | Slot::Level(_) => 1,
+            Slot::Choice | Slot::ChoiceWith(_) => 2,
         }
     }
 
-    fn choice() -> Self {
-        let l1 = Level::random();
-        let mut l2 = l1;
-        while l2 == l1 { l2 = Level::random(); }
-        RouteElement::Choice(l1, l2)
+    /// Levels of a new column, [edges] being the children of each parent
+    fn levels(&self, edges: &[Vec<usize>], width: usize, template: &RouteTemplate, rng: &mut RunRng) -> Vec<Level> {
+        match *self {
+            Slot::Single(l) | Slot::Level(l) => vec![l; width],
+            Slot::Choice => {
+                // Siblings are neighbours: only check the previous node
+                let mut levels: Vec<Level> = vec![];
+                for _ in 0..width {
+                    let mut l = template.random_level(rng);
+                    while levels.last() == Some(&l) { l = template.random_level(rng); }
+                    levels.push(l);
+                }
+                levels
+            }
+            Slot::ChoiceWith(with) => {
+                let mut levels: Vec<Option<Level>> = vec![None; width];
+                for children in edges {
+                    let (a, b) = (children[0], children[1]);
+                    let (fixed, other) = match levels[a] {
+                        // Shared with the previous parent
+                        Some(l) if l == with => (a, b),
+                        Some(_) => (b, a),
+                        None if rng.next_u32() % 2 == 0 => (a, b),
+                        None => (b, a),
+                    };
+                    levels[fixed] = Some(with);
+                    if levels[other].is_none() {
+                        let mut l = with;
+                        while l == with { l = template.random_level(rng); }
+                        levels[other] = Some(l);
+                    }
+                }
+                levels.into_iter().map(|l| l.unwrap_or(with)).collect()
+            }
+        }
     }
+}
 
-    fn choice_with(l1: Level) -> Self {
-        let mut l2 = l1;
-        while l2 == l1 { l2 = Level::random(); }
-        if thread_rng().next_u32() % 2 == 1 { RouteElement::Choice(l1, l2) }
-        else { RouteElement::Choice(l2, l1) }
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteNode {
+    pub level: Level,
+    /// Indices of the reachable nodes in the next column
+    pub next: Vec<usize>,
 }
 
+impl RouteNode {
+    fn new(level: Level) -> Self { Self { level, next: vec![] } }
+}
+
+/// Star map: columns of nodes, each node being linked to some nodes of the next column.
 #[derive(Debug)]
-pub struct Route(pub [RouteElement; 28]);
+pub struct Route(pub Vec<Vec<RouteNode>>);
 
 impl Route {
-    pub fn act_len() -> usize { 9 }
-
-    fn new() -> Self {
-        let mut route = vec![];
-        for i in 0..27 {
-            let element = match i % 9 {
-                3 => RouteElement::Level(Level::Upgrade),
-                7 => RouteElement::choice_with(Level::Repair),
-                8 => RouteElement::Level(Level::Boss),
-                i if i % 2 == 0 => RouteElement::Level(Level::Space),
-                _ => RouteElement::choice(),
+    fn new(template: &RouteTemplate, mode: GameMode, rng: &mut RunRng) -> Self {
+        let mut route = Route(vec![]);
+        for _ in 0..template.acts { route.push_act(template, rng); }
+        // Endless routes grow with [Route::push_act] instead
+        if mode != GameMode::Endless { route.push_column(Slot::Single(Level::Win), template, rng); }
+        route
+    }
+
+    fn push_act(&mut self, template: &RouteTemplate, rng: &mut RunRng) {
+        for &slot in template.slots.iter() { self.push_column(slot, template, rng); }
+    }
+
+    fn push_column(&mut self, slot: Slot, template: &RouteTemplate, rng: &mut RunRng) {
+        let parents = self.0.last().map_or(1, |column| column.len());
+        let children = slot.children();
+
+        // Neighbour parents may share a child, which keeps edges from crossing
+        let mut edges: Vec<Vec<usize>> = vec![];
+        let mut width = 0;
+        for p in 0..parents {
+            let shared = p > 0 && match slot {
+                Slot::Single(_) => true,
+                _ => rng.next_u32() % 2 == 0,
             };
-            route.push(element);
+            let first = if shared { width - 1 } else { width };
+            edges.push((first..first + children).collect());
+            width = first + children;
         }
-        route.push(RouteElement::Level(Level::Win));
-        return Route(route.try_into().expect(""))
+
+        let levels = slot.levels(&edges, width, template, rng);
+        if let Some(column) = self.0.last_mut() {
+            for (node, next) in column.iter_mut().zip(edges) { node.next = next; }
+        }
+        self.0.push(levels.into_iter().map(RouteNode::new).collect());
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Deserialize)]
 pub enum GameMode {
     Standard,
     Act2,
     Act3,
     LastBoss,
     BossRush,
+    /// Acts are generated forever, enemies get stronger after each loop
+    Endless,
+    /// Same seed, ship and items for everyone on a given day, see [crate::logic::daily]
+    Daily,
+    /// Single fights picked from the practice screen, see [crate::logic::practice]
+    Practice,
 }
 
 impl GameMode {
@@ -158,65 +218,274 @@ impl GameMode {
             GameMode::Act3 => "Act 3",
             GameMode::LastBoss => "Last Boss",
             GameMode::BossRush => "Boss Rush",
+            GameMode::Endless => "Endless",
+            GameMode::Daily => "Daily",
+            GameMode::Practice => "Practice",
         }
     }
 
+    /// Whether the [crate::logic::director::Director] can be switched on in the hangar,
+    /// daily challenges and practice fights stay the same for everyone
+    pub fn adaptive(&self) -> bool { !matches!(self, GameMode::Daily | GameMode::Practice) }
+
     pub fn next(&self) -> Self {
         match self {
             GameMode::Standard => GameMode::Act2,
             GameMode::Act2 => GameMode::Act3,
             GameMode::Act3 => GameMode::LastBoss,
             GameMode::LastBoss => GameMode::BossRush,
-            GameMode::BossRush => GameMode::Standard,
+            GameMode::BossRush => GameMode::Endless,
+            GameMode::Endless => GameMode::Daily,
+            GameMode::Daily => GameMode::Practice,
+            GameMode::Practice => GameMode::Standard,
         }
     }
+}
 
-    fn accepts(&self, level: (RouteElement, usize)) -> bool {
-        if level.0 == RouteElement::Level(Level::Win) { return true; }
-        match self {
-            GameMode::Standard => true,
-            GameMode::Act2 => level.0 == RouteElement::Level(Level::Upgrade) || level.1 >= Route::act_len(),
-            GameMode::Act3 => level.0 == RouteElement::Level(Level::Upgrade) || level.1 >= Route::act_len() * 2,
-            GameMode::LastBoss => level.0 == RouteElement::Level(Level::Upgrade) || level.1 >= Route::act_len() * 2 + Route::act_len() - 1,
-            GameMode::BossRush => level.0 == RouteElement::Level(Level::Upgrade) || level.0 == RouteElement::Level(Level::Boss),
+/// Columns kept by a [GameMode], the others being skipped
+#[derive(Clone, Debug, Deserialize)]
+struct ModeFilter {
+    /// Columns made only of these levels are always kept
+    #[serde(default)]
+    keep: Vec<Level>,
+    /// (act, slot) of the first column kept, [None] to only keep [ModeFilter::keep]
+    #[serde(default)]
+    from: Option<(usize, usize)>,
+}
+
+/// Layout of the acts of a run, loaded from `assets/data/acts.route.ron`
+#[derive(Clone, Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "3f5d8a4e-51c7-4b8e-9d62-0c1a7e93b2f4"]
+#[serde(try_from = "RouteTemplateFile")]
+pub struct RouteTemplate {
+    /// Number of columns in an act
+    pub act_len: usize,
+    pub acts: usize,
+    /// Generation rule of each slot of an act
+    slots: Vec<Slot>,
+    /// Weighted levels offered by [Slot::Choice] and [Slot::ChoiceWith]
+    random: Vec<(Level, u32)>,
+    /// Weighted levels an [Level::Unknown] node can turn into
+    unknown: Vec<(Level, u32)>,
+    modes: HashMap<GameMode, ModeFilter>,
+}
+
+/// [RouteTemplate] as written in the asset, before validation
+#[derive(Deserialize)]
+struct RouteTemplateFile {
+    act_len: usize,
+    acts: usize,
+    /// Slots with a specific rule
+    slots: HashMap<usize, Slot>,
+    /// Rule of the other even slots
+    even: Slot,
+    /// Rule of the other odd slots
+    odd: Slot,
+    random: Vec<(Level, u32)>,
+    unknown: Vec<(Level, u32)>,
+    modes: HashMap<GameMode, ModeFilter>,
+}
+
+impl TryFrom<RouteTemplateFile> for RouteTemplate {
+    type Error = String;
+
+    fn try_from(file: RouteTemplateFile) -> Result<Self, Self::Error> {
+        if file.act_len == 0 { return Err("act_len must be at least 1".to_string()); }
+        if file.acts == 0 { return Err("acts must be at least 1".to_string()); }
+
+        if let Some(i) = file.slots.keys().filter(|&&i| i >= file.act_len).min() {
+            return Err(format!("slot {} is out of range (act_len is {})", i, file.act_len));
+        }
+        let slots: Vec<Slot> = (0..file.act_len)
+            .map(|i| file.slots.get(&i).copied().unwrap_or(if i % 2 == 0 { file.even } else { file.odd }))
+            .collect();
+
+        let is_fight = |slot: &Slot| matches!(slot, Slot::Single(Level::Space) | Slot::Level(Level::Space));
+        for (i, slot) in slots.iter().enumerate() {
+            match slot {
+                Slot::Single(Level::Win) | Slot::Level(Level::Win) | Slot::ChoiceWith(Level::Win) =>
+                    return Err(format!("slot {}: Win is reserved for the end of the route", i)),
+                // Only fights let the player pick the next level
+                Slot::Choice | Slot::ChoiceWith(_) if !is_fight(&slots[(i + file.act_len - 1) % file.act_len]) =>
+                    return Err(format!("slot {}: a choice must follow a fight slot", i)),
+                _ => {}
+            }
+        }
+        if !slots.iter().any(|slot| matches!(slot, Slot::Single(Level::Boss) | Slot::Level(Level::Boss))) {
+            return Err("an act must have a Boss slot".to_string());
+        }
+
+        for (name, table) in [("random", &file.random), ("unknown", &file.unknown)] {
+            if table.iter().any(|&(_, weight)| weight == 0) {
+                return Err(format!("{}: weights must be positive", name));
+            }
+            if table.iter().any(|&(level, _)| level == Level::Win) {
+                return Err(format!("{}: Win is reserved for the end of the route", name));
+            }
+        }
+        // Siblings of a choice are always different
+        if !file.random.iter().any(|&(level, _)| level != file.random[0].0) {
+            return Err("random: needs at least 2 different levels".to_string());
         }
+        if file.unknown.is_empty() {
+            return Err("unknown: needs at least 1 level".to_string());
+        }
+        if let Some(&(level, _)) = file.unknown.iter().find(|&&(level, _)| level == Level::Unknown || level == Level::Boss) {
+            return Err(format!("unknown: can't contain {:?}", level));
+        }
+
+        let mut mode = GameMode::Standard;
+        loop {
+            match file.modes.get(&mode) {
+                None => return Err(format!("modes: missing filter for {:?}", mode)),
+                Some(ModeFilter { from: Some((act, slot)), .. }) if *act >= file.acts || *slot >= file.act_len =>
+                    return Err(format!("modes: {:?} starts at act {} slot {}, which is out of range", mode, act, slot)),
+                _ => {}
+            }
+            mode = mode.next();
+            if mode == GameMode::Standard { break; }
+        }
+
+        Ok(RouteTemplate { act_len: file.act_len, acts: file.acts, slots, random: file.random, unknown: file.unknown, modes: file.modes })
+    }
+}
+
+impl RouteTemplate {
+    fn random_level(&self, rng: &mut RunRng) -> Level {
+        self.random.choose_weighted(rng, |&(_, weight)| weight).unwrap().0
+    }
+
+    pub fn unknown_level(&self, rng: &mut RunRng) -> Level {
+        self.unknown.choose_weighted(rng, |&(_, weight)| weight).unwrap().0
+    }
+
+    fn accepts(&self, mode: GameMode, column: &[RouteNode], index: usize) -> bool {
+        let is = |level: &Level| column.iter().all(|node| node.level == *level);
+        if is(&Level::Win) { return true; }
+        let filter = &self.modes[&mode];
+        filter.keep.iter().any(is) || filter.from.is_some_and(|(act, slot)| index >= act * self.act_len + slot)
     }
 }
 
 #[derive(Resource, Debug)]
 pub struct CurrentRoute {
     pub route: Route,
+    /// Current column of the route
     pub level: usize,
+    /// Current node in the column
+    pub node: usize,
+    /// (column, node) of every node visited so far
+    pub visited: Vec<(usize, usize)>,
     pub lost: bool,
+    /// Resolved state of the current node
     pub chosen: GameState,
     pub mode: GameMode,
+    template: RouteTemplate,
     angry_shopkeepers: bool,
 }
 
 impl CurrentRoute {
-    pub fn new(mode: GameMode) -> Self {
-        let mut cr = CurrentRoute { route: Route::new(), level: 0, lost: false, chosen: GameState::Dummy, mode, angry_shopkeepers: false };
-        if !cr.mode.accepts((cr.route.0[cr.level], cr.level)) { cr.advance(); }
+    pub fn new(mode: GameMode, template: &RouteTemplate, rng: &mut RunRng) -> Self {
+        let route = Route::new(template, mode, rng);
+        let mut cr = CurrentRoute { route, level: 0, node: 0, visited: vec![], lost: false, chosen: GameState::Dummy, mode, template: template.clone(), angry_shopkeepers: false };
+        if !cr.template.accepts(mode, &cr.route.0[0], 0) { cr.advance(rng); }
+        else { cr.go_to(0, 0, rng); }
         cr
     }
 
-    pub fn advance(&mut self) {
-        self.level += 1;
-        if !self.mode.accepts((self.route.0[self.level], self.level)) { self.advance(); }
+    pub fn current(&self) -> &RouteNode { &self.route.0[self.level][self.node] }
+
+    /// Next column accepted by the game mode, and its nodes reachable from the current node
+    pub fn next(&self) -> (usize, Vec<usize>) { self.next_from(self.level, self.node) }
+
+    fn next_from(&self, level: usize, node: usize) -> (usize, Vec<usize>) {
+        let mut level = level;
+        let mut nodes = vec![node];
+        loop {
+            let mut next: Vec<usize> = nodes.iter()
+                .flat_map(|&n| self.route.0[level][n].next.iter().copied())
+                .collect();
+            next.sort();
+            next.dedup();
+            level += 1;
+            nodes = next;
+            if self.template.accepts(self.mode, &self.route.0[level], level) { return (level, nodes); }
+        }
+    }
+
+    /// Go to the first reachable node, used when there is no choice
+    pub fn advance(&mut self, rng: &mut RunRng) {
+        let (level, nodes) = self.next();
+        self.go_to(level, nodes[0], rng);
+    }
+
+    /// Skip the next node, unless it is a boss or the player would lose a choice,
+    /// [CurrentRoute::advance] being called right after
+    pub fn skip(&mut self, rng: &mut RunRng) {
+        let (level, nodes) = self.next();
+        let [node] = nodes[..] else { return; };
+        if [Level::Boss, Level::Win].contains(&self.route.0[level][node].level) { return; }
+        if self.next_from(level, node).1.len() > 1 { return; }
+        self.go_to(level, node, rng);
+    }
+
+    /// Go to a node of the next column picked by the player
+    pub fn choose(&mut self, node: usize, rng: &mut RunRng) {
+        let (level, _) = self.next();
+        self.go_to(level, node, rng);
     }
 
+    fn go_to(&mut self, level: usize, node: usize, rng: &mut RunRng) {
+        self.level = level;
+        self.node = node;
+        self.visited.push((level, node));
+
+        // Always keep the next act ready so that [CurrentRoute::next] never runs out of columns
+        if self.mode == GameMode::Endless && level + self.act_len() >= self.route.0.len() {
+            let act = self.route.0.len() / self.act_len();
+            self.route.push_act(&self.template, rng.at_act(act));
+        }
+        let level = match self.current().level {
+            Level::Unknown => self.template.unknown_level(rng.at_level(level)),
+            level => level,
+        };
+        self.chosen = level.state();
+    }
+
+    /// Go straight to the first node of [level], used by the practice screen
+    pub fn jump(&mut self, level: usize, state: GameState) {
+        self.level = level;
+        self.node = 0;
+        self.visited = vec![(level, 0)];
+        self.chosen = state;
+    }
+
+    pub fn is_visited(&self, level: usize, node: usize) -> bool { self.visited.contains(&(level, node)) }
+
     pub fn state(&self) -> GameState {
         if self.level >= self.route.0.len() { return GameState::Hangar; }
         if self.lost { return GameState::GameOver; }
 
-        let s = match self.route.0[self.level].state() {
-            Some(state) => state,
-            None => self.chosen,
-        };
-        if self.angry_shopkeepers && s == GameState::Shop { GameState::Elite } else { s }
+        if self.angry_shopkeepers && self.chosen == GameState::Shop { GameState::Elite } else { self.chosen }
+    }
+
+    pub fn act_len(&self) -> usize { self.template.act_len }
+
+    pub fn act(&self) -> usize { self.level / self.act_len() + 1 }
+
+    /// Acts cleared in this run, the acts skipped by the game mode don't count
+    pub fn acts_cleared(&self) -> usize {
+        let start = self.template.modes[&self.mode].from.map_or(0, |(act, _)| act);
+        (self.level / self.act_len()).saturating_sub(start)
     }
 
-    pub fn act(&self) -> usize { self.level / Route::act_len() + 1 }
+    /// Number of times all the acts of the template were cleared, only relevant in [GameMode::Endless]
+    pub fn loops(&self) -> usize { (self.act() - 1) / self.template.acts }
+
+    /// Column in the current loop of [GameMode::Endless], so that level-based scaling doesn't add up with [CurrentRoute::loops]
+    pub fn loop_level(&self) -> usize {
+        if self.mode == GameMode::Endless { self.level % (self.template.acts * self.act_len()) } else { self.level }
+    }
 
     pub fn win(&self) -> bool { self.level == self.route.0.len() - 1 }
 
@@ -224,11 +493,120 @@ impl CurrentRoute {
     pub fn are_shopkeepers_angry(&mut self) -> bool { self.angry_shopkeepers }
 }
 
+#[cfg(test)]
+fn template() -> RouteTemplate {
+    ron::from_str(include_str!("../../assets/data/acts.route.ron")).unwrap()
+}
+
 #[test]
 fn show_route() {
-    let route = CurrentRoute::new(GameMode::Standard);
-    for (i, element) in route.route.0.iter().enumerate() {
-        if i % 9 == 0 { println!(); }
-        println!("{} – {:?}", i, element);
+    let template = template();
+    let route = CurrentRoute::new(GameMode::Standard, &template, &mut RunRng::new(42));
+    for (i, column) in route.route.0.iter().enumerate() {
+        if i % template.act_len == 0 { println!(); }
+        println!("{} – {:?}", i, column);
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn same_seed_same_route() {
+    let template = template();
+    let seed = 42;
+    let mut r1 = CurrentRoute::new(GameMode::Standard, &template, &mut RunRng::new(seed));
+    let mut r2 = CurrentRoute::new(GameMode::Standard, &template, &mut RunRng::new(seed));
+    assert_eq!(r1.route.0, r2.route.0);
+
+    let (mut rng1, mut rng2) = (RunRng::new(seed), RunRng::new(seed));
+    while !r1.win() {
+        let last = *r1.next().1.last().unwrap();
+        r1.choose(last, &mut rng1);
+        r2.choose(last, &mut rng2);
+        assert_eq!(r1.state(), r2.state());
+    }
+}
+
+#[test]
+fn route_follows_act_structure() {
+    let template = template();
+    for seed in 0..100 {
+        let route = Route::new(&template, GameMode::Standard, &mut RunRng::new(seed));
+        assert_eq!(route.0.len(), template.act_len * template.acts + 1);
+        for (i, column) in route.0.iter().enumerate() {
+            let next_width = route.0.get(i + 1).map_or(0, |c| c.len());
+            for node in column {
+                // Only fights can lead to a choice
+                let max_next = if node.level == Level::Space { 2 } else { 1 };
+                assert!(node.next.len() <= max_next);
+                assert!(node.next.iter().all(|&n| n < next_width));
+                if next_width > 0 { assert!(!node.next.is_empty()); }
+            }
+            match i % template.act_len {
+                3 => assert_eq!(column, &vec![RouteNode { level: Level::Upgrade, next: vec![0] }]),
+                8 => assert!(column.len() == 1 && column[0].level == Level::Boss),
+                7 => assert!(route.0[i - 1].iter().all(|parent| parent.next.iter().any(|&n| column[n].level == Level::Repair))),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[test]
+fn invalid_templates_are_rejected() {
+    crate::util::assert_rejected::<RouteTemplate>(include_str!("../../assets/data/acts.route.ron"), &[
+        ("8: Single(Boss),", "", "Boss slot"),
+        ("3: Single(Upgrade),", "12: Single(Upgrade),", "slot 12 is out of range"),
+        ("(Shop, 2),", "(Boss, 2),", "can't contain Boss"),
+    ]);
+}
+
+#[test]
+fn endless_route_keeps_growing() {
+    let template = template();
+    let seed = 42;
+    let mut r1 = CurrentRoute::new(GameMode::Endless, &template, &mut RunRng::new(seed));
+    let mut r2 = CurrentRoute::new(GameMode::Endless, &template, &mut RunRng::new(seed));
+
+    let (mut rng1, mut rng2) = (RunRng::new(seed), RunRng::new(seed));
+    while r1.loops() < 2 {
+        assert!(!r1.win());
+        let first = r1.next().1[0];
+        r1.choose(first, &mut rng1);
+        r2.choose(first, &mut rng2);
+        assert_eq!(r1.state(), r2.state());
+    }
+    assert_eq!(r1.route.0, r2.route.0);
+    assert_eq!(r1.act(), template.acts * 2 + 1);
+    assert_eq!(r1.loop_level(), r1.level - template.acts * template.act_len * 2);
+}
+
+#[test]
+fn skipping_keeps_choices() {
+    let template = template();
+    let mut rng = RunRng::new(42);
+    let mut route = CurrentRoute::new(GameMode::Standard, &template, &mut rng);
+
+    // Event after the first fight: the next fight leads to the upgrade station
+    route.jump(1, GameState::Event);
+    route.skip(&mut rng);
+    route.advance(&mut rng);
+    assert_eq!(route.level, 3);
+
+    // Event after the upgrade station: the next fight leads to a choice, which is kept
+    route.jump(5, GameState::Event);
+    route.skip(&mut rng);
+    route.advance(&mut rng);
+    assert_eq!(route.level, 6);
+    assert_eq!(route.next().1.len(), 2);
+}
+
+#[test]
+fn skipped_acts_are_not_cleared() {
+    let template = template();
+    for (mode, acts) in [(GameMode::Standard, 3), (GameMode::Act3, 1), (GameMode::LastBoss, 1)] {
+        let mut rng = RunRng::new(42);
+        let mut route = CurrentRoute::new(mode, &template, &mut rng);
+        assert_eq!(route.acts_cleared(), 0);
+        while !route.win() { route.advance(&mut rng); }
+        assert_eq!(route.acts_cleared(), acts);
+    }
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Per-user directory of the save files, [None] if the platform doesn't have one
+fn path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("gbjam11").join(file))
+}
+
+/// Content of the save [file], [None] if it doesn't exist yet or can't be read
+pub fn load<T: DeserializeOwned>(file: &str) -> Option<T> {
+    let content = fs::read_to_string(path(file)?).ok()?;
+    ron::from_str(&content)
+        .map_err(|e| warn!("Couldn't read {}: {}", file, e))
+        .ok()
+}
+
+pub fn save<T: Serialize>(file: &str, value: &T) {
+    let Some(path) = path(file) else {
+        warn!("No data directory to save {}", file);
+        return;
+    };
+    let result = ron::to_string(value)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            if let Some(dir) = path.parent() { fs::create_dir_all(dir).map_err(|e| e.to_string())?; }
+            fs::write(&path, content).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result { warn!("Couldn't save {}: {}", path.display(), e); }
+}
@@ -0,0 +1,237 @@
+use std::fmt::Debug;
+use std::fs;
+
+use bevy::asset::FileAssetIo;
+use bevy::math::{Vec2, vec2};
+use serde::{Serialize, Serializer};
+use serde::de::DeserializeOwned;
+
+use crate::entities::{ShipRegistry, Ships};
+use crate::GameState;
+use crate::logic::movement::{Formation, Moves};
+use crate::logic::rng::RunRng;
+use crate::logic::route::RouteTemplate;
+use crate::logic::wave::{CurrentWave, WaveContext, WaveEvent, WaveTemplates};
+use crate::util::{HALF_HEIGHT, HALF_WIDTH, HEIGHT};
+
+/// Simulation step, the game runs at 60 FPS
+const STEP: f32 = 1. / 60.;
+/// Enemies still on screen after this long never leave
+const MAX_LIFETIME: f32 = 120.;
+/// Duration simulated after an elite or boss spawns, the fight lasts until it is killed
+const ELITE_FIGHT_DURATION: f32 = 60.;
+/// Levels still running after this long never end
+const MAX_DURATION: f32 = 300.;
+
+const USAGE: &str = "Usage: dump-waves <space|elite|boss> <level> <seed> [--json]";
+
+/// Written as its [Debug] text
+fn debug<T: Debug, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:?}", value))
+}
+
+/// Enemy spawned during a level
+#[derive(Serialize)]
+pub struct TimelineSpawn {
+    /// Game time of the spawn, in seconds
+    pub t: f32,
+    #[serde(serialize_with = "debug")]
+    pub model: Ships,
+    #[serde(serialize_with = "debug")]
+    pub moves: Moves,
+    /// Offset from the path of [TimelineSpawn::moves] for formation members
+    pub offset: Vec2,
+    /// Game time when the enemy leaves the screen if it isn't killed, [None] if it never leaves
+    pub exit: Option<f32>,
+}
+
+impl TimelineSpawn {
+    fn new(t: f32, model: Ships, moves: Moves, ships: &ShipRegistry) -> Self {
+        let speed = ships.ship(model).speed;
+        Self::with_offset(t, model, moves, speed, Vec2::ZERO)
+    }
+
+    fn with_offset(t: f32, model: Ships, moves: Moves, speed: f32, offset: Vec2) -> Self {
+        let center = vec2(HALF_WIDTH, HALF_HEIGHT);
+        let mut simulated = moves.clone();
+        let mut exit = None;
+        let mut dt = 0.;
+        while dt < MAX_LIFETIME {
+            dt += STEP;
+            // Same rule as [crate::logic::movement::despawn_far_ships]
+            if (simulated.pos(dt, STEP, speed) + offset).distance(center) > HEIGHT as f32 {
+                exit = Some(t + dt);
+                break;
+            }
+        }
+        TimelineSpawn { t, model, moves, offset, exit }
+    }
+}
+
+/// Spawns of a level with absolute timestamps, assuming that no enemy is killed and that
+/// elites and bosses stay at full health
+#[derive(Serialize)]
+pub struct WaveTimeline {
+    #[serde(serialize_with = "debug")]
+    pub state: GameState,
+    pub level: usize,
+    /// Act of the level, starting at 0
+    pub act: usize,
+    pub seed: u64,
+    pub spawns: Vec<TimelineSpawn>,
+    /// Time until the events of the level are done, [None] if they never are
+    pub duration: Option<f32>,
+    pub max_enemies: usize,
+}
+
+impl WaveTimeline {
+    /// Generate the wave like [crate::logic::wave] does when entering the level, from a freshly seeded level rng.
+    ///
+    /// This is an approximation: in a run, resolving a [crate::logic::route::Level::Unknown] node draws from the same
+    /// stream before the wave, elite affixes draw from it during the fight, and the [crate::logic::director::Director]
+    /// may change the waves.
+    pub fn new(state: GameState, waves: &WaveTemplates, route: &RouteTemplate, ships: &ShipRegistry, level: usize, seed: u64) -> Self {
+        let act = level / route.act_len;
+        let loops = act / route.acts;
+        let mut rng = RunRng::new(seed);
+        rng.at_level(level);
+        let mut wave = CurrentWave::new(&state, waves, None, level, act, loops, &mut rng);
+
+        let mut spawns: Vec<TimelineSpawn> = vec![];
+        let mut t = 0.;
+        let mut duration = None;
+        let mut elite_since = None;
+        while t < MAX_DURATION && elite_since.map_or(true, |since| t - since < ELITE_FIGHT_DURATION) {
+            let alive = |spawn: &&TimelineSpawn| spawn.exit.map_or(true, |exit| exit > t);
+            let context = WaveContext {
+                dt: STEP * 1000.,
+                kills: 0,
+                enemies: spawns.iter().filter(alive).count(),
+                clear: !spawns.iter().any(|spawn| alive(&spawn)),
+                elite_health: spawns.iter().filter(alive).any(|spawn| spawn.model.is_elite()).then_some(1.),
+            };
+            let mut events = vec![];
+            let done = wave.track.update(&context, &mut events);
+            for event in events {
+                match event {
+                    WaveEvent::Spawn(model, moves) => {
+                        if model.is_elite() && elite_since.is_none() { elite_since = Some(t); }
+                        spawns.push(TimelineSpawn::new(t, model, moves, ships));
+                    }
+                    WaveEvent::SpawnCrossing(y, right) => {
+                        let model = waves.random_enemy(act, &mut rng);
+                        spawns.push(TimelineSpawn::new(t, model, Moves::random_crossing_dir(y, right, &mut rng), ships));
+                    }
+                    WaveEvent::SpawnFormation(models, shape, moves) => {
+                        let formation = Formation::new(shape, &models, &moves, ships);
+                        for (slot, &model) in models.iter().enumerate() {
+                            let offset = formation.offset(slot, models.len());
+                            spawns.push(TimelineSpawn::with_offset(t, model, moves.clone(), formation.speed, offset));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if done {
+                duration = Some(t);
+                break;
+            }
+            t += STEP;
+        }
+
+        let max_enemies = max_enemies(&spawns);
+        WaveTimeline { state, level, act, seed, spawns, duration, max_enemies }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!("{:?} level {} (act {}), seed {}\n", self.state, self.level, self.act + 1, self.seed);
+        for spawn in self.spawns.iter() {
+            let exit = spawn.exit.map_or("never leaves".to_string(), |exit| format!("leaves at {:.2}s", exit));
+            let offset = if spawn.offset == Vec2::ZERO { String::new() } else { format!(" + {}", spawn.offset) };
+            text.push_str(&format!("{:>7.2}s  {:?}  {:?}{}  {}\n", spawn.t, spawn.model, spawn.moves, offset, exit));
+        }
+        let duration = self.duration.map_or("until the enemies are killed".to_string(), |d| format!("{:.2}s", d));
+        text.push_str(&format!("Duration: {}\n", duration));
+        text.push_str(&format!("Max simultaneous enemies: {}\n", self.max_enemies));
+        text
+    }
+
+    /// Models, moves and the state are written as their [Debug] text
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("timelines are plain data") + "\n"
+    }
+}
+
+/// Maximum number of enemies on screen at the same time
+fn max_enemies(spawns: &[TimelineSpawn]) -> usize {
+    let mut changes: Vec<(f32, isize)> = vec![];
+    for spawn in spawns.iter() {
+        changes.push((spawn.t, 1));
+        if let Some(exit) = spawn.exit { changes.push((exit, -1)); }
+    }
+    // Enemies leaving are counted before the ones spawning at the same time
+    changes.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    let mut enemies = 0;
+    let mut max = 0;
+    for (_, change) in changes {
+        enemies += change;
+        max = max.max(enemies);
+    }
+    max as usize
+}
+
+fn load<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let path = FileAssetIo::get_base_path().join("assets").join(path);
+    let file = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    ron::from_str(&file).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// `cargo run -- dump-waves <space|elite|boss> <level> <seed> [--json]`
+///
+/// Print the timeline of a level without starting the game, to review difficulty curves
+/// and diff generator changes.
+pub fn dump(args: &[String]) -> Result<(), String> {
+    let state = match args.first().map(|s| s.to_lowercase()).as_deref() {
+        Some("space") => GameState::Space,
+        Some("elite") => GameState::Elite,
+        Some("boss") => GameState::Boss,
+        _ => return Err(USAGE.to_string()),
+    };
+    let level = args.get(1).and_then(|l| l.parse().ok()).ok_or(USAGE)?;
+    let seed = args.get(2).and_then(|s| s.parse().ok()).ok_or(USAGE)?;
+    let json = match args.get(3).map(String::as_str) {
+        None => false,
+        Some("--json") => true,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let waves: WaveTemplates = load("data/space.waves.ron")?;
+    let route: RouteTemplate = load("data/acts.route.ron")?;
+    let ships: ShipRegistry = load("data/fleet.ships.ron")?;
+    let timeline = WaveTimeline::new(state, &waves, &route, &ships, level, seed);
+    print!("{}", if json { timeline.json() } else { timeline.text() });
+    Ok(())
+}
+
+#[test]
+fn same_seed_same_timeline() {
+    let waves = ron::from_str(include_str!("../../assets/data/space.waves.ron")).unwrap();
+    let route = ron::from_str(include_str!("../../assets/data/acts.route.ron")).unwrap();
+    let ships = crate::entities::registry();
+    let seed = 42;
+    for level in [0, 10, 20] {
+        let t1 = WaveTimeline::new(GameState::Space, &waves, &route, &ships, level, seed);
+        let t2 = WaveTimeline::new(GameState::Space, &waves, &route, &ships, level, seed);
+        assert_eq!(t1.json(), t2.json());
+        let json: serde_json::Value = serde_json::from_str(&t1.json()).unwrap();
+        assert_eq!(json["level"], level);
+        assert_eq!(json["spawns"].as_array().unwrap().len(), t1.spawns.len());
+        assert_eq!(json["spawns"][0]["moves"].as_str().unwrap(), format!("{:?}", t1.spawns[0].moves));
+        assert!(t1.duration.is_some_and(|d| d > 0.));
+        assert!(t1.max_enemies >= 1);
+    }
+    let boss = WaveTimeline::new(GameState::Boss, &waves, &route, &ships, 8, seed);
+    assert!(boss.duration.is_none());
+    assert!(serde_json::from_str::<serde_json::Value>(&boss.json()).unwrap()["duration"].is_null());
+    assert!(matches!(boss.spawns[0].model, Ships::Boss(_)));
+}
@@ -1,10 +1,11 @@
 use bevy::prelude::{Commands, Component, DetectChanges, Entity, EventWriter, Query, Res, ResMut, Time, Transform, With};
-use rand::{Rng, RngCore, thread_rng};
+use rand::{Rng, RngCore};
 
 use crate::entities::{MainShip, MuteShotsFor, Ship, Shot};
 use crate::graphics::FakeTransform;
 use crate::logic::{Items, ShipStatus};
 use crate::logic::damage::KillCount;
+use crate::logic::rng::RunRng;
 use crate::music::{PlaySFXEvent, SFX};
 use crate::util::{HEIGHT, upgrades, WIDTH};
 
@@ -126,14 +127,12 @@ impl Upgrades {
         }
     }
 
-    pub fn random_stat_upgrade() -> Self {
-        let mut rng = thread_rng();
+    pub fn random_stat_upgrade(rng: &mut RunRng) -> Self {
         let options = [Upgrades::Speed, Upgrades::ShotSpeed, Upgrades::ShotFrequency, Upgrades::Damage, Upgrades::Hull];
         options[rng.gen_range(0..options.len())]
     }
 
-    fn random_non_stat_upgrade() -> Self {
-        let mut rng = thread_rng();
+    fn random_non_stat_upgrade(rng: &mut RunRng) -> Self {
         let options = [
             Upgrades::BouncingShots,
             Upgrades::PiercingShots,
@@ -147,22 +146,21 @@ impl Upgrades {
         options[rng.gen_range(0..options.len())]
     }
 
-    pub fn new_non_stat_upgrade(status: &ShipStatus) -> Self {
-        let mut upgrade = Self::random_non_stat_upgrade();
+    pub fn new_non_stat_upgrade(status: &ShipStatus, rng: &mut RunRng) -> Self {
+        let mut upgrade = Self::random_non_stat_upgrade(rng);
         for i in 0..=30 {
-            if status.has_upgrade(upgrade) { upgrade = Self::random_non_stat_upgrade(); }
-            else if i == 30 { upgrade = Self::random_stat_upgrade(); }
+            if status.has_upgrade(upgrade) { upgrade = Self::random_non_stat_upgrade(rng); }
+            else if i == 30 { upgrade = Self::random_stat_upgrade(rng); }
             else { break }
         }
         upgrade
     }
 
-    pub fn new_upgrade(status: &ShipStatus) -> Self {
-        let mut rng = thread_rng();
+    pub fn new_upgrade(status: &ShipStatus, rng: &mut RunRng) -> Self {
         if rng.next_u32() % 3 == 0 {
-            Upgrades::new_non_stat_upgrade(status)
+            Upgrades::new_non_stat_upgrade(status, rng)
         } else {
-            Upgrades::random_stat_upgrade()
+            Upgrades::random_stat_upgrade(rng)
         }
     }
 }
@@ -1,21 +1,30 @@
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::vec;
 
 use bevy::app::App;
+use bevy::ecs::system::EntityCommands;
 use bevy::math::{vec2, vec3};
 use bevy::prelude::*;
-use rand::{Rng, thread_rng};
-use rand::rngs::ThreadRng;
+use bevy::reflect::{TypePath, TypeUuid};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
 
-use crate::entities::{MainShip, Ship, Ships, ShipWeapons};
+use crate::entities::{BulletPatterns, MainShip, Ship, ShipRegistry, Ships, ShipWeapons};
 use crate::GameState;
 use crate::graphics::FakeTransform;
 use crate::graphics::sizes::Hitbox;
-use crate::logic::{elite, Loot};
-use crate::logic::movement::{Movement, Moves};
-use crate::logic::route::CurrentRoute;
-use crate::screens::Textures;
-use crate::util::{HALF_HEIGHT, HALF_WIDTH, HEIGHT, in_states, space, WIDTH, z_pos};
+use crate::logic::{boss, BossPhases, elite, Loot};
+use crate::logic::affix::Affixes;
+use crate::logic::boss::BossParts;
+use crate::logic::damage::DamageEvent;
+use crate::logic::director::Director;
+use crate::logic::movement::{Formation, FormationMember, FormationShape, Movement, Moves};
+use crate::logic::rng::RunRng;
+use crate::logic::practice::{Practice, PracticeFight};
+use crate::logic::route::{CurrentRoute, GameMode};
+use crate::screens::{Data, Textures};
+use crate::util::{HALF_HEIGHT, HEIGHT, in_states, space, WIDTH, z_pos};
 
 pub struct WavePlugin;
 
@@ -43,39 +52,155 @@ pub struct ShipBundle {
 }
 
 impl ShipBundle {
-    pub fn from(atlas: Handle<TextureAtlas>, model: Ships, pos: Vec2) -> ShipBundle {
-        let ship = Ship::from(model);
+    pub fn from(atlas: Handle<TextureAtlas>, ships: &ShipRegistry, model: Ships, pos: Vec2) -> ShipBundle {
+        let ship = ships.ship(model);
+        let template = ships.get(model);
         Self {
             sprite: SpriteSheetBundle {
                 sprite: TextureAtlasSprite {
-                    index: ship.sprite_index(),
+                    index: template.sprite,
                     ..default()
                 },
-                transform: Transform::from_scale(vec3(ship.scale(), ship.scale(), 1.)),
+                transform: Transform::from_scale(vec3(ship.scale, ship.scale, 1.)),
                 texture_atlas: atlas,
                 ..default()
             },
             pos: FakeTransform::from_xyz(pos.x, pos.y, z_pos::SHIPS),
-            weapons: ShipWeapons::new(&ship, model.weapons()),
-            hitbox: model.hitbox(),
-            loot: Loot { credits: space::credits(model) },
+            weapons: ShipWeapons::new(&ship, template.weapons.clone()),
+            hitbox: Hitbox(template.hitbox),
+            loot: Loot { credits: template.loot },
             ship,
         }
     }
+
+    /// Stronger enemies for each loop completed in [crate::logic::route::GameMode::Endless]
+    pub fn with_loops(mut self, loops: usize) -> Self {
+        if loops == 0 || self.ship.friendly { return self; }
+        self.ship.max_health *= space::loop_health_ratio(loops);
+        self.ship.health = self.ship.max_health;
+        self.ship.shot_frequency *= space::loop_shot_frequency_ratio(loops);
+        self.weapons = self.weapons.with_fire_rate(space::loop_shot_frequency_ratio(loops));
+        self
+    }
 }
 
-#[derive(Clone)]
-enum WaveEvent {
+#[derive(Clone, Debug)]
+pub enum WaveEvent {
     Spawn(Ships, Moves),
+    /// Ships flying around an anchor following the [Moves]
+    SpawnFormation(Vec<Ships>, FormationShape, Moves),
+    /// Random enemy crossing the screen (y / left to right)
+    SpawnCrossing(f32, bool),
     WaitMilliseconds(usize),
+    /// Wait until no ship is left on screen
     WaitForClear,
+    WaitFor(Trigger),
+    /// Run tracks of events at the same time, until they are all done
+    Tracks(Vec<Vec<WaveEvent>>),
+    /// Start the track over
+    Repeat,
+}
+
+/// Condition for a [WaveEvent::WaitFor] to be done
+#[derive(Clone, Debug)]
+pub enum Trigger {
+    /// Milliseconds since the wait started, paced by the [Director]
+    After(usize),
+    /// Enemies killed since the wait started
+    Kills(usize),
+    /// Fewer enemies than this on screen
+    FewerThan(usize),
+    /// An elite or boss on screen below this ratio of its max health
+    EliteBelow(f32),
+    Any(Vec<Trigger>),
+}
+
+impl Trigger {
+    /// [wait] is the time elapsed since the wait started, and the kills when it started
+    fn reached(&self, wait: (f32, usize), context: &WaveContext) -> bool {
+        match self {
+            Trigger::After(ms) => wait.0 >= *ms as f32,
+            Trigger::Kills(n) => context.kills - wait.1 >= *n,
+            Trigger::FewerThan(n) => context.enemies < *n,
+            Trigger::EliteBelow(ratio) => context.elite_health.is_some_and(|health| health < *ratio),
+            Trigger::Any(triggers) => triggers.iter().any(|trigger| trigger.reached(wait, context)),
+        }
+    }
+}
+
+/// Track spawning a random enemy crossing the screen every [delay] ms, forever
+pub fn infinite_wave(delay: usize, y: f32, right: bool) -> Vec<WaveEvent> {
+    vec![WaveEvent::WaitMilliseconds(delay), WaveEvent::SpawnCrossing(y, right), WaveEvent::Repeat]
+}
+
+/// State of the level watched by the [Trigger]s
+pub(crate) struct WaveContext {
+    /// Milliseconds since the previous update, paced by the [Director]
+    pub(crate) dt: f32,
+    /// Enemies killed since the start of the level
+    pub(crate) kills: usize,
+    pub(crate) enemies: usize,
+    /// No ship on screen, friendly ones included
+    pub(crate) clear: bool,
+    /// Lowest health ratio of the elites and bosses on screen
+    pub(crate) elite_health: Option<f32>,
 }
 
+/// Events run one after the other
 #[derive(Clone)]
-pub enum SpecialEvent {
-    Spawn(Ships, Moves),
-    /// Spawn enemies continuously (delay / y / right / timer)
-    InfiniteWave(usize, f32, bool, f32),
+pub(crate) struct Track {
+    events: Vec<WaveEvent>,
+    next: usize,
+    /// Time elapsed and kills when the wait at [Track::next] started
+    wait: Option<(f32, usize)>,
+    /// Running tracks of the [WaveEvent::Tracks] at [Track::next]
+    tracks: Vec<Track>,
+}
+
+impl Track {
+    pub(crate) fn new(events: Vec<WaveEvent>) -> Self { Track { events, next: 0, wait: None, tracks: vec![] } }
+
+    fn waited(wait: &mut Option<(f32, usize)>, trigger: &Trigger, context: &WaveContext) -> bool {
+        let wait = wait.get_or_insert((0., context.kills));
+        wait.0 += context.dt;
+        trigger.reached(*wait, context)
+    }
+
+    /// Run the events until one of them waits, spawn events are added to [spawns].
+    /// Returns true once the track is done.
+    pub(crate) fn update(&mut self, context: &WaveContext, spawns: &mut Vec<WaveEvent>) -> bool {
+        while let Some(event) = self.events.get(self.next) {
+            match event {
+                WaveEvent::Spawn(..) | WaveEvent::SpawnFormation(..) | WaveEvent::SpawnCrossing(..) => spawns.push(event.clone()),
+                WaveEvent::WaitMilliseconds(ms) => {
+                    if !Self::waited(&mut self.wait, &Trigger::After(*ms), context) { return false; }
+                }
+                WaveEvent::WaitForClear => {
+                    // Ships spawned during this update aren't on screen yet
+                    if !context.clear || !spawns.is_empty() { return false; }
+                }
+                WaveEvent::WaitFor(trigger) => {
+                    if !Self::waited(&mut self.wait, trigger, context) { return false; }
+                }
+                WaveEvent::Tracks(tracks) => {
+                    if self.tracks.is_empty() { self.tracks = tracks.iter().cloned().map(Track::new).collect(); }
+                    let mut done = true;
+                    for track in self.tracks.iter_mut() { done = track.update(context, spawns) && done; }
+                    if !done { return false; }
+                    self.tracks.clear();
+                }
+                WaveEvent::Repeat => {
+                    // Start over on the next update, in case the track never waits
+                    self.next = 0;
+                    self.wait = None;
+                    return false;
+                }
+            }
+            self.next += 1;
+            self.wait = None;
+        }
+        true
+    }
 }
 
 enum WavePart {
@@ -83,29 +208,30 @@ enum WavePart {
     ConsecutiveWithPause(u8, f32, usize),
     Same(u8, usize, Ships, Moves),
     Parallel(usize, Vec<WavePart>),
+    Formation(Vec<Ships>, FormationShape, Moves),
 }
 
 impl Default for WavePart {
     fn default() -> Self { WavePart::SimpleEnemy }
 }
 
-fn random_y(rng: &mut ThreadRng) -> f32 { HEIGHT as f32 / 5. * 2. + rng.gen_range(0.0..1.0) * HALF_HEIGHT }
+fn random_y(rng: &mut RunRng) -> f32 { HEIGHT as f32 / 5. * 2. + rng.gen_range(0.0..1.0) * HALF_HEIGHT }
 
 impl WavePart {
-    fn events(&self, level: usize, base_y: f32) -> Vec<WaveEvent> {
+    fn events(&self, waves: &WaveTemplates, act: usize, base_y: f32, rng: &mut RunRng) -> Vec<WaveEvent> {
         let mut events = vec![];
         match self {
             WavePart::SimpleEnemy => {
                 events.push(WaveEvent::Spawn(
-                    Ships::random_enemy(level),
-                    Moves::random_crossing(base_y),
+                    waves.random_enemy(act, rng),
+                    Moves::random_crossing(base_y, rng),
                 ));
             }
             WavePart::ConsecutiveWithPause(n, x, pause) => {
-                let base_move = Moves::random_crossing(base_y);
+                let base_move = Moves::random_crossing(base_y, rng);
                 for _ in 0..*n {
                     events.push(WaveEvent::Spawn(
-                        Ships::random_enemy(level),
+                        waves.random_enemy(act, rng),
                         Moves::WithPause(*x, *pause as f32 / 1000., 0., Box::new(base_move.clone())),
                     ));
                     events.push(WaveEvent::WaitMilliseconds(*pause + 2000));
@@ -117,135 +243,219 @@ impl WavePart {
                     events.push(WaveEvent::WaitMilliseconds(*pause));
                 }
             }
+            WavePart::Formation(models, shape, moves) => {
+                events.push(WaveEvent::SpawnFormation(models.clone(), *shape, moves.clone()));
+            }
             WavePart::Parallel(pause, parts) => {
-                let mut rng = thread_rng();
                 let mut y_pos: Vec<f32> = vec![-100.];
-                let mut parallel = vec![];
+                let mut tracks = vec![];
                 for (i, part) in parts.iter().enumerate() {
                     let mut y = -100.;
                     while y_pos.iter().any(|existing| (y - *existing).abs() < 18.) {
-                        y = random_y(&mut rng);
+                        y = random_y(rng);
                     }
                     y_pos.push(y);
-                    events.push(WaveEvent::WaitMilliseconds(i * *pause));
-                    events.append(&mut part.events(level, y));
-                    parallel.push(events.clone());
-                    events.clear();
+                    let mut track = vec![WaveEvent::WaitMilliseconds(i * *pause)];
+                    track.append(&mut part.events(waves, act, y, rng));
+                    tracks.push(track);
                 }
-                events = merge_waves(parallel.as_slice());
+                events.push(WaveEvent::Tracks(tracks));
             }
         }
         events
     }
+}
 
-    fn random(level: usize) -> Self {
-        let mut rng = thread_rng();
-        let mut possible_parts = match level {
-            0..=8 => vec![
-                WavePart::SimpleEnemy,
-                WavePart::ConsecutiveWithPause(2, HALF_WIDTH, 4000),
-                WavePart::Same(
-                    2, 4000,
-                    Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT - 8.)..(HALF_HEIGHT + 24.)))
-                ),
-                WavePart::Parallel(8000, vec![
-                    WavePart::SimpleEnemy,
-                    WavePart::ConsecutiveWithPause(2, HALF_WIDTH, 4000),
-                ]),
-                WavePart::Parallel(4000, vec![
-                    WavePart::SimpleEnemy,
-                    WavePart::SimpleEnemy,
-                ]),
-                WavePart::Parallel(5000, vec![
-                    WavePart::SimpleEnemy,
-                    WavePart::SimpleEnemy,
-                    WavePart::SimpleEnemy,
-                ]),
-            ],
-            9..=17 => vec![
-                WavePart::ConsecutiveWithPause(3, HALF_WIDTH, 4000),
-                WavePart::ConsecutiveWithPause(4, HALF_WIDTH, 3500),
-                WavePart::ConsecutiveWithPause(2, HALF_WIDTH, 0),
-                WavePart::ConsecutiveWithPause(3, HALF_WIDTH, 0),
-                WavePart::Same(
-                    2, 3000,
-                    Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT - 8.)..(HALF_HEIGHT + 24.)))
-                ),
-                WavePart::Same(
-                    3, 4500,
-                    Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT - 8.)..(HALF_HEIGHT + 24.)))
-                ),
-                WavePart::Parallel(8000, vec![
-                    WavePart::Same(
-                        2, 3000,
-                        Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT - 8.)..(HALF_HEIGHT + 8.)))
-                    ),
-                    WavePart::Same(
-                        2, 3000,
-                        Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT + 16.)..(HALF_HEIGHT + 32.)))
-                    ),
-                ]),
-                WavePart::Parallel(8000, vec![
-                    WavePart::ConsecutiveWithPause(2, HALF_WIDTH, 4000),
-                    WavePart::ConsecutiveWithPause(2, HALF_WIDTH, 4000),
-                ]),
-                WavePart::Parallel(8000, vec![
-                    WavePart::ConsecutiveWithPause(3, HALF_WIDTH / 3., 4000),
-                    WavePart::ConsecutiveWithPause(3, HALF_WIDTH / 3. * 2., 4000),
-                ]),
-            ],
-            _ => vec![
-                WavePart::ConsecutiveWithPause(3, HALF_WIDTH, 0),
-                WavePart::ConsecutiveWithPause(4, HALF_WIDTH, 0),
-                WavePart::Same(
-                    4, 5000,
-                    Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT - 8.)..(HALF_HEIGHT + 24.)))
-                ),
-                WavePart::Parallel(8000, vec![
-                    WavePart::Same(
-                        3, 3500,
-                        Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT - 8.)..(HALF_HEIGHT + 8.)))
-                    ),
-                    WavePart::Same(
-                        3, 3500,
-                        Ships::random_enemy(level), Moves::random_crossing(rng.gen_range((HALF_HEIGHT + 16.)..(HALF_HEIGHT + 32.)))
-                    ),
-                ]),
-                WavePart::Parallel(8000, vec![
-                    WavePart::ConsecutiveWithPause(4, HALF_WIDTH / 3., 3500),
-                    WavePart::ConsecutiveWithPause(4, HALF_WIDTH / 3. * 2., 3500),
-                ]),
-                WavePart::ConsecutiveWithPause(4, HALF_WIDTH, 3500),
-                WavePart::ConsecutiveWithPause(5, HALF_WIDTH, 3500),
-            ],
-        };
-        possible_parts.remove(rng.gen_range(0..possible_parts.len()))
+/// Movement of a [PartTemplate::Same] or [PartTemplate::Formation] part
+#[derive(Clone, Debug, Deserialize)]
+enum MoveTemplate {
+    /// [Moves::random_crossing] at a random height in [y], relative to the middle of the screen
+    Crossing { y: (f32, f32) },
+}
+
+impl MoveTemplate {
+    fn moves(&self, rng: &mut RunRng) -> Moves {
+        match *self {
+            MoveTemplate::Crossing { y: (min, max) } => Moves::random_crossing(HALF_HEIGHT + rng.gen_range(min..max), rng),
+        }
+    }
+}
+
+/// [WavePart] as written in the asset, other parts are referenced by name
+#[derive(Clone, Debug, Deserialize)]
+enum PartTemplate {
+    Simple,
+    Consecutive { n: u8, x: f32, pause: usize },
+    Same { n: u8, pause: usize, moves: String },
+    Parallel { pause: usize, parts: Vec<String> },
+    Formation { n: u8, shape: FormationShape, moves: String },
+}
+
+/// Levels starting at [WaveBand::from] until the next band
+#[derive(Clone, Debug, Deserialize)]
+struct WaveBand {
+    from: usize,
+    /// Number of parts in a wave
+    patterns: usize,
+    /// Weighted names of the parts, drawn for each pattern
+    parts: Vec<(String, u32)>,
+}
+
+/// Waves of [GameState::Space] levels, loaded from `assets/data/space.waves.ron`
+#[derive(Clone, Debug, Deserialize, TypeUuid, TypePath)]
+#[uuid = "8b0e2f6c-93d4-4a1e-b7c5-2d6f19e4a08b"]
+#[serde(try_from = "WaveTemplatesFile")]
+pub struct WaveTemplates {
+    /// Weighted invaders of each act, later acts use the last pool
+    enemies: Vec<Vec<(u8, u32)>>,
+    moves: HashMap<String, MoveTemplate>,
+    parts: HashMap<String, PartTemplate>,
+    /// Sorted by [WaveBand::from], the first one starting at level 0
+    bands: Vec<WaveBand>,
+}
+
+/// [WaveTemplates] as written in the asset, before validation
+#[derive(Deserialize)]
+struct WaveTemplatesFile {
+    enemies: Vec<Vec<(u8, u32)>>,
+    moves: HashMap<String, MoveTemplate>,
+    parts: HashMap<String, PartTemplate>,
+    bands: Vec<WaveBand>,
+}
+
+impl TryFrom<WaveTemplatesFile> for WaveTemplates {
+    type Error = String;
+
+    fn try_from(file: WaveTemplatesFile) -> Result<Self, Self::Error> {
+        if file.enemies.is_empty() { return Err("enemies: needs at least 1 act".to_string()); }
+        for (act, pool) in file.enemies.iter().enumerate() {
+            if pool.is_empty() { return Err(format!("enemies: act {} has no invader", act)); }
+            if pool.iter().any(|&(_, weight)| weight == 0) {
+                return Err(format!("enemies: act {}: weights must be positive", act));
+            }
+            // Required models are checked against the ship registry
+            if let Some(&(invader, _)) = pool.iter().find(|&&(invader, _)| !Ships::required().any(|model| model == Ships::Invader(invader))) {
+                return Err(format!("enemies: act {}: invader {} doesn't exist", act, invader));
+            }
+        }
+
+        for (name, moves) in file.moves.iter() {
+            match *moves {
+                MoveTemplate::Crossing { y: (min, max) } if min >= max =>
+                    return Err(format!("moves: {}: empty y range", name)),
+                _ => {}
+            }
+        }
+
+        for (name, part) in file.parts.iter() {
+            match part {
+                PartTemplate::Consecutive { n: 0, .. } | PartTemplate::Same { n: 0, .. } | PartTemplate::Formation { n: 0, .. } =>
+                    return Err(format!("parts: {}: needs at least 1 enemy", name)),
+                PartTemplate::Same { moves, .. } | PartTemplate::Formation { moves, .. } if !file.moves.contains_key(moves) =>
+                    return Err(format!("parts: {}: unknown moves {}", name, moves)),
+                PartTemplate::Formation { shape: FormationShape::Grid(0, _), .. } =>
+                    return Err(format!("parts: {}: a grid needs at least 1 column", name)),
+                PartTemplate::Parallel { parts, .. } => {
+                    if parts.is_empty() { return Err(format!("parts: {}: needs at least 1 part", name)); }
+                    for part in parts.iter() {
+                        match file.parts.get(part) {
+                            None => return Err(format!("parts: {}: unknown part {}", name, part)),
+                            Some(PartTemplate::Parallel { .. }) =>
+                                return Err(format!("parts: {}: can't contain the Parallel part {}", name, part)),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        match file.bands.first() {
+            None => return Err("bands: needs at least 1 band".to_string()),
+            Some(band) if band.from != 0 => return Err("bands: the first band must start at level 0".to_string()),
+            _ => {}
+        }
+        for (i, band) in file.bands.iter().enumerate() {
+            if i > 0 && band.from <= file.bands[i - 1].from {
+                return Err(format!("bands: band {} must start after the previous one", i));
+            }
+            if band.patterns == 0 { return Err(format!("bands: band {} needs at least 1 pattern", i)); }
+            if band.parts.is_empty() { return Err(format!("bands: band {} needs at least 1 part", i)); }
+            for (part, weight) in band.parts.iter() {
+                if !file.parts.contains_key(part) { return Err(format!("bands: band {}: unknown part {}", i, part)); }
+                if *weight == 0 { return Err(format!("bands: band {}: weights must be positive", i)); }
+            }
+        }
+
+        Ok(WaveTemplates { enemies: file.enemies, moves: file.moves, parts: file.parts, bands: file.bands })
     }
 }
 
+impl WaveTemplates {
+    /// [act] starts at 0
+    pub fn random_enemy(&self, act: usize, rng: &mut RunRng) -> Ships {
+        let pool = &self.enemies[act.min(self.enemies.len() - 1)];
+        Ships::Invader(pool.choose_weighted(rng, |&(_, weight)| weight).unwrap().0)
+    }
+
+    fn band(&self, level: usize) -> &WaveBand {
+        self.bands.iter().rev().find(|band| band.from <= level).unwrap()
+    }
+
+    fn part(&self, name: &str, act: usize, rng: &mut RunRng) -> WavePart {
+        match &self.parts[name] {
+            PartTemplate::Simple => WavePart::SimpleEnemy,
+            PartTemplate::Consecutive { n, x, pause } => WavePart::ConsecutiveWithPause(*n, *x, *pause),
+            PartTemplate::Same { n, pause, moves } =>
+                WavePart::Same(*n, *pause, self.random_enemy(act, rng), self.moves[moves].moves(rng)),
+            PartTemplate::Parallel { pause, parts } =>
+                WavePart::Parallel(*pause, parts.iter().map(|part| self.part(part, act, rng)).collect()),
+            PartTemplate::Formation { n, shape, moves } => WavePart::Formation(
+                (0..*n).map(|_| self.random_enemy(act, rng)).collect(),
+                *shape,
+                self.moves[moves].moves(rng),
+            ),
+        }
+    }
+
+    fn random_part(&self, level: usize, act: usize, rng: &mut RunRng) -> WavePart {
+        let name = &self.band(level).parts.choose_weighted(rng, |(_, weight)| *weight).unwrap().0;
+        self.part(name, act, rng)
+    }
+}
+
+/// Events of the level, act (starting at 0) of the level and endless loops
 #[derive(Resource)]
-struct CurrentWave(Vec<WaveEvent>, Vec<SpecialEvent>, usize);
+pub(crate) struct CurrentWave {
+    pub(crate) track: Track,
+    act: usize,
+    loops: usize,
+    /// Enemies killed since the start of the level
+    kills: usize,
+    /// The events are done, or the elite was killed
+    done: bool,
+}
 
 impl CurrentWave {
-    pub fn new(state: &GameState, level: usize) -> Self {
+    pub fn new(state: &GameState, waves: &WaveTemplates, director: Option<&Director>, level: usize, act: usize, loops: usize, rng: &mut RunRng) -> Self {
         info!("{:?} – Generating events for level {}:", state, level);
 
-        let (wave, special) = match state {
-            GameState::Elite => (vec![], elite::gen_elite_wave(level)),
-            GameState::Boss => (vec![], elite::gen_boss_wave(level)),
-            _ => (Self::gen_space_wave(level), vec![]),
+        let events = match state {
+            GameState::Elite => elite::gen_elite_wave(level, rng),
+            GameState::Boss => elite::gen_boss_wave(act),
+            _ => Self::gen_space_wave(waves, level, director.map_or(act, |d| d.enemy_act(act)), rng),
         };
 
-        CurrentWave(wave, special, level)
+        CurrentWave { track: Track::new(events), act, loops, kills: 0, done: false }
     }
 
-    fn gen_space_wave(level: usize) -> Vec<WaveEvent> {
-        let mut rng = thread_rng();
+    fn gen_space_wave(waves: &WaveTemplates, level: usize, act: usize, rng: &mut RunRng) -> Vec<WaveEvent> {
         let mut wave = vec![];
 
-        for _ in 0..space::patterns_nb(level) {
-            let wave_part = WavePart::random(level);
-            wave.append(&mut wave_part.events(level, random_y(&mut rng)));
+        for _ in 0..waves.band(level).patterns {
+            let wave_part = waves.random_part(level, act, rng);
+            let y = random_y(rng);
+            wave.append(&mut wave_part.events(waves, act, y, rng));
             // Always end wave with [WaveEvent::WaitForClear]
             wave.push(WaveEvent::WaitForClear);
         }
@@ -260,8 +470,23 @@ fn enter(
     mut commands: Commands,
     route: Res<CurrentRoute>,
     state: Res<State<GameState>>,
+    practice: Res<Practice>,
+    data: Res<Data>,
+    waves: Res<Assets<WaveTemplates>>,
+    mut director: Option<ResMut<Director>>,
+    mut rng: ResMut<RunRng>,
 ) {
-    commands.insert_resource(CurrentWave::new(state.get(), route.level));
+    let waves = waves.get(&data.waves).unwrap();
+    if let Some(director) = director.as_mut() {
+        director.new_fight();
+        info!("Director – intensity {:.2}: enemies of act {}", director.intensity(), director.enemy_act(route.act() - 1) + 1);
+    }
+    let mut wave = CurrentWave::new(state.get(), waves, director.as_deref(), route.level, route.act() - 1, route.loops(), rng.at_level(route.level));
+    // The practice screen picks the elite variant
+    if let (GameMode::Practice, PracticeFight::Elite(variant)) = (route.mode, practice.fight()) {
+        wave.track = Track::new(elite::elite_wave(variant));
+    }
+    commands.insert_resource(wave);
 }
 
 #[derive(Event)]
@@ -272,115 +497,147 @@ fn update(
     time: Res<Time>,
     textures: Res<Textures>,
     mut wave: ResMut<CurrentWave>,
-    ships: Query<&Ship, Without<MainShip>>,
+    ships: Query<(&Ship, Option<&BossParts>), Without<MainShip>>,
     mut cleared: EventWriter<WaveCleared>,
     mut elite_killed: EventReader<EliteKilled>,
+    mut damage: EventReader<DamageEvent>,
+    data: Res<Data>,
+    waves: Res<Assets<WaveTemplates>>,
+    patterns: Res<Assets<BulletPatterns>>,
+    registry: Res<Assets<ShipRegistry>>,
+    director: Option<Res<Director>>,
+    mut rng: ResMut<RunRng>,
 ) {
-    let mut next = false;
-    let level = wave.2;
-
-    match wave.0.get_mut(0) {
-        None => {}
-        Some(WaveEvent::Spawn(model, moves)) => {
-            commands
-                .spawn(ShipBundle::from(
-                    textures.ship.clone(),
-                    model.clone(),
-                    *moves.starting_pos(),
-                ))
-                .insert(Movement {
-                    moves: moves.clone(),
-                    t_0: time.elapsed_seconds(),
-                })
-            ;
-            next = true;
-        }
-        Some(WaveEvent::WaitMilliseconds(ref mut s)) => {
-            if *s > 0 {
-                let dt = (time.delta_seconds() * 1000.) as usize;
-                *s = if *s < dt { 0 } else { *s - dt };
-            }
-            else { next = true; }
-        }
-        Some(WaveEvent::WaitForClear) => {
-            if ships.is_empty() {
-                next = true;
-                if wave.0.len() == 1 { cleared.send(WaveCleared); }
-            }
-        }
+    let (act, loops) = (wave.act, wave.loops);
+    // The director speeds up or slows down the waits between spawns
+    let pace = director.as_ref().map_or(1., |d| d.pace());
+    let enemy_act = director.as_ref().map_or(act, |d| d.enemy_act(act));
+
+    // Killing the elite ends the level, see [crate::logic::damage::despawn_ships]
+    if !elite_killed.is_empty() { elite_killed.clear(); wave.done = true; }
+
+    wave.kills += damage.iter()
+        .filter(|event| event.fatal && ships.get(event.ship).is_ok_and(|(ship, _)| !ship.friendly))
+        .count();
+    if wave.done { return; }
+
+    let context = WaveContext {
+        dt: time.delta_seconds() * pace * 1000.,
+        kills: wave.kills,
+        enemies: ships.iter().filter(|(ship, _)| !ship.friendly).count(),
+        clear: ships.is_empty(),
+        elite_health: ships.iter()
+            .filter(|(ship, _)| ship.model.is_elite())
+            .map(|(ship, parts)| boss::health_ratio(ship, parts))
+            .reduce(f32::min),
+    };
+    let mut spawns = vec![];
+    if wave.track.update(&context, &mut spawns) {
+        wave.done = true;
+        cleared.send(WaveCleared);
     }
 
-    if next { wave.0.remove(0); }
-
-    if !elite_killed.is_empty() { elite_killed.clear(); wave.1.clear(); }
-
-    next = false;
-    match wave.1.get_mut(0) {
-        Some(SpecialEvent::Spawn(model, moves)) => {
-            commands
-                .spawn(ShipBundle::from(
-                    textures.ship.clone(),
-                    model.clone(),
-                    *moves.starting_pos(),
-                ))
-                .insert(Movement {
-                    moves: moves.clone(),
-                    t_0: time.elapsed_seconds(),
-                })
-            ;
-            next = true;
-        }
-        Some(SpecialEvent::InfiniteWave(delay, y, right, timer)) => {
-            let delay = *delay as f32 / 1000.;
-            let t = *timer;
-            let dt = time.delta_seconds();
-            if ((t / delay) as usize) < (((t + dt) / delay) as usize) {
-                commands
-                    .spawn(ShipBundle::from(
-                        textures.ship.clone(),
-                        Ships::random_enemy(level),
-                        if *right { vec2(-16., *y) } else { vec2(WIDTH as f32 + 16., *y) },
-                    ))
-                    .insert(Movement {
-                        moves: Moves::random_crossing_dir(*y, *right),
-                        t_0: time.elapsed_seconds(),
-                    })
-                ;
+    let (patterns, registry) = (patterns.get(&data.patterns).unwrap(), registry.get(&data.ships).unwrap());
+    let t_0 = time.elapsed_seconds();
+    for event in spawns {
+        match event {
+            WaveEvent::Spawn(model, moves) => {
+                let mut ship = spawn_ship(&mut commands, &textures, registry, patterns, model, *moves.starting_pos(), loops);
+                ship.insert(Movement { moves, t_0 });
+                if let Ships::Boss(n) = model { ship.insert(BossPhases::new(elite::boss_phases(n))); }
+                if let Ships::Elite(_) = model {
+                    let affixes = Affixes::roll(act, loops, &mut rng);
+                    info!("{:?} – affixes {:?}", model, affixes.affixes);
+                    ship.insert(Loot { credits: registry.get(model).loot + affixes.credits() }).insert(affixes);
+                }
             }
-            *timer += time.delta_seconds();
+            WaveEvent::SpawnCrossing(y, right) => {
+                let model = waves.get(&data.waves).unwrap().random_enemy(enemy_act, &mut rng);
+                let pos = if right { vec2(-16., y) } else { vec2(WIDTH as f32 + 16., y) };
+                spawn_ship(&mut commands, &textures, registry, patterns, model, pos, loops)
+                    .insert(Movement { moves: Moves::random_crossing_dir(y, right, &mut rng), t_0 });
+            }
+            WaveEvent::SpawnFormation(models, shape, moves) => {
+                let formation = Formation::new(shape, &models, &moves, registry);
+                let start = *moves.starting_pos();
+                let anchor = commands
+                    .spawn(FakeTransform::from_xyz(start.x, start.y, z_pos::SHIPS))
+                    .insert(Movement { moves, t_0 })
+                    .id();
+                for (slot, &model) in models.iter().enumerate() {
+                    spawn_ship(&mut commands, &textures, registry, patterns, model, start + formation.offset(slot, models.len()), loops)
+                        .insert(FormationMember { anchor, slot });
+                }
+                commands.entity(anchor).insert(formation);
+            }
+            _ => {}
         }
-        None => {}
     }
+}
 
-    if next { wave.1.remove(0); }
+/// Ships firing a pattern have no weapons in their [ShipRegistry] template, see [BulletPatterns::check_ships]
+pub(crate) fn spawn_ship<'w, 's, 'a>(commands: &'a mut Commands<'w, 's>, textures: &Textures, ships: &ShipRegistry, patterns: &BulletPatterns, model: Ships, pos: Vec2, loops: usize) -> EntityCommands<'w, 's, 'a> {
+    let bundle = ShipBundle::from(textures.ship.clone(), ships, model, pos).with_loops(loops);
+    let pattern = patterns.of(model);
+    let mut ship = commands.spawn(bundle);
+    if let Some(pattern) = pattern { ship.insert(pattern); }
+    ship
 }
 
-fn merge_waves(waves: &[Vec<WaveEvent>]) -> Vec<WaveEvent> {
-    let mut events = BTreeMap::<usize, Vec<WaveEvent>>::new();
-    for wave in waves {
-        let mut pause = 0;
-        for event in wave.iter() {
-            match event {
-                WaveEvent::WaitMilliseconds(s) => { pause += *s; }
-                WaveEvent::WaitForClear => {}
-                _ => {
-                    if let Some(e) = events.get_mut(&pause) {
-                        e.push(event.clone());
-                    } else {
-                        events.insert(pause, vec![event.clone()]);
-                    }
-                }
-            }
-        }
-    }
+#[cfg(test)]
+fn templates() -> WaveTemplates {
+    ron::from_str(include_str!("../../assets/data/space.waves.ron")).unwrap()
+}
+
+#[test]
+fn waves_follow_level_bands() {
+    let waves = templates();
+    assert_eq!(waves.band(0).patterns, 2);
+    assert_eq!(waves.band(17).patterns, 3);
+    assert_eq!(waves.band(100).patterns, 4);
 
-    let mut merged = vec![];
-    let mut last_pause = 0;
-    for (p, e) in events.iter() {
-        let wait = *p - last_pause;
-        last_pause = *p;
-        merged.push(WaveEvent::WaitMilliseconds(wait));
-        e.iter().for_each(|event| merged.push(event.clone()));
+    let mut rng = RunRng::new(42);
+    for level in 0..30 {
+        let wave = CurrentWave::new(&GameState::Space, &waves, None, level, level / 9, 0, &mut rng);
+        let events = &wave.track.events;
+        let clears = events.iter().filter(|event| matches!(event, WaveEvent::WaitForClear)).count();
+        assert_eq!(clears, waves.band(level).patterns);
+        assert!(events.iter().any(|event| !matches!(event, WaveEvent::WaitForClear | WaveEvent::WaitMilliseconds(_))));
     }
-    merged
-}
\ No newline at end of file
+}
+
+#[test]
+fn invalid_waves_are_rejected() {
+    crate::util::assert_rejected::<WaveTemplates>(include_str!("../../assets/data/space.waves.ron"), &[
+        ("moves: \"cross_low\")", "moves: \"zigzag\")", "unknown moves zigzag"),
+        ("[\"simple\", \"row_2\"]", "[\"simple\", \"simple_x2\"]", "can't contain the Parallel part"),
+        ("from: 9,", "from: 20,", "must start after the previous one"),
+        ("(8, 3)", "(9, 3)", "invader 9 doesn't exist"),
+        ("Grid(2, 14.)", "Grid(0, 14.)", "a grid needs at least 1 column"),
+    ]);
+}
+
+#[test]
+fn triggers_escalate_fights() {
+    let context = |kills: usize, elite_health: f32| WaveContext { dt: 100., kills, enemies: 1, clear: false, elite_health: Some(elite_health) };
+    let mut track = Track::new(vec![
+        WaveEvent::Tracks(vec![
+            infinite_wave(1000, 0., true),
+            vec![WaveEvent::WaitFor(Trigger::EliteBelow(0.5)), WaveEvent::SpawnCrossing(0., false)],
+        ]),
+    ]);
+    let mut spawns = vec![];
+    for _ in 0..30 { track.update(&context(0, 1.), &mut spawns); }
+    assert_eq!(spawns.len(), 3);
+    assert!(spawns.iter().all(|spawn| matches!(spawn, WaveEvent::SpawnCrossing(_, true))));
+
+    spawns.clear();
+    track.update(&context(0, 0.4), &mut spawns);
+    assert!(matches!(spawns[..], [WaveEvent::SpawnCrossing(_, false)]));
+
+    // Kills are counted from the start of the wait
+    let mut track = Track::new(vec![WaveEvent::WaitFor(Trigger::Kills(2))]);
+    assert!(!track.update(&context(5, 1.), &mut spawns));
+    assert!(!track.update(&context(6, 1.), &mut spawns));
+    assert!(track.update(&context(7, 1.), &mut spawns));
+}
@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use bevy::asset::ChangeWatcher;
 use bevy::prelude::*;
 
 use crate::entities::EntitiesPlugin;
@@ -26,7 +29,10 @@ pub enum GameState {
     Shop,
     Upgrade,
     Repair,
+    Event,
+    Casino,
     SimpleText,
+    Practice,
     GameOver,
     /// Dummy state to fix Space -> Space transition
     Dummy,
@@ -37,11 +43,14 @@ impl GameState {
         match self {
             GameState::Title => Some(BGM::Title),
             GameState::Hangar
-            | GameState::Upgrade => Some(BGM::Hangar),
+            | GameState::Practice
+            | GameState::Upgrade
+            | GameState::Event => Some(BGM::Hangar),
             GameState::Space => Some(BGM::Space),
             GameState::Elite => Some(BGM::Elite),
             GameState::Boss => Some(BGM::Boss),
             GameState::Shop
+            | GameState::Casino
             | GameState::GameOver => Some(BGM::Shop),
             GameState::Repair => Some(BGM::Repair),
             _ => None,
@@ -52,10 +61,30 @@ impl GameState {
 fn main() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
 
+    // Headless tools
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("dump-waves") {
+        if let Err(error) = logic::timeline::dump(&args[1..]) {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+        return;
+    }
+
     App::new()
         .insert_resource(Msaa::Off)
         .add_plugins(DefaultPlugins
             .set(ImagePlugin::default_nearest())
+            .set(AssetPlugin {
+                // Reload the data files when they are modified, so that they can be tweaked while playing.
+                // Levels already generated keep the previous version.
+                watch_for_changes: if cfg!(all(debug_assertions, not(target_arch = "wasm32"))) {
+                    ChangeWatcher::with_delay(Duration::from_millis(200))
+                } else {
+                    None
+                },
+                ..default()
+            })
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     resolution: (
@@ -0,0 +1,253 @@
+use bevy::app::App;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use rand::seq::SliceRandom;
+
+use crate::{GameState, util};
+use crate::graphics::{ScreenTransition, StarsSpeed, TextStyles};
+use crate::logic::event::Effect;
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
+use crate::logic::ShipStatus;
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::{Fonts, Textures};
+use crate::screens::map::map_closed;
+use crate::screens::shop::Select;
+use crate::util::{casino, HALF_WIDTH, HEIGHT, z_pos};
+
+pub struct CasinoPlugin;
+
+#[derive(Component)]
+struct CasinoUI;
+
+#[derive(Component)]
+struct CreditsText;
+
+#[derive(Component)]
+struct ResultText;
+
+#[derive(Component)]
+struct SelectionDot;
+
+#[derive(Component, Copy, Clone, PartialEq)]
+enum CasinoOption {
+    Slots,
+    Crate(usize),
+    Leave,
+}
+
+impl CasinoOption {
+    fn text(&self) -> String {
+        match self {
+            CasinoOption::Slots => format!("Slots ({} credits)", casino::SLOTS_BET),
+            CasinoOption::Crate(n) => format!("Crate {} ({} credits)", n + 1, casino::CRATE_BET),
+            CasinoOption::Leave => "Leave".to_string(),
+        }
+    }
+}
+
+/// Content of the crates, rolled when entering the casino, and the crate opened if any
+#[derive(Resource)]
+struct Crates(Vec<Effect>, Option<usize>);
+
+impl Plugin for CasinoPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, update.run_if(in_state(GameState::Casino).and_then(map_closed)))
+            .add_systems(OnEnter(GameState::Casino), enter)
+            .add_systems(OnExit(GameState::Casino), exit)
+        ;
+    }
+}
+
+fn update(
+    keys: Res<Input<KeyCode>>,
+    mut options: ResMut<Select<CasinoOption>>,
+    mut crates: ResMut<Crates>,
+    mut status: ResMut<ShipStatus>,
+    mut route: ResMut<CurrentRoute>,
+    mut transition: ResMut<ScreenTransition>,
+    mut credits_text: Query<&mut Text, (With<CreditsText>, Without<ResultText>)>,
+    mut result_text: Query<&mut Text, (With<ResultText>, Without<CreditsText>)>,
+    mut dot: Query<&mut Transform, With<SelectionDot>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+    mut rng: ResMut<RunRng>,
+) {
+    if !transition.is_none() { return; }
+    let Ok(mut credits_text) = credits_text.get_single_mut() else { return; };
+    let Ok(mut result_text) = result_text.get_single_mut() else { return; };
+    let Ok(mut dot_pos) = dot.get_single_mut() else { return; };
+
+    // Select previous / next option
+    if keys.just_pressed(KeyCode::Up) {
+        options.selected = (options.items.len() + options.selected - 1) % options.items.len();
+        sfx.send(PlaySFXEvent(SFX::Left));
+    } else if keys.just_pressed(KeyCode::Down) {
+        options.selected = (options.selected + 1) % options.items.len();
+        sfx.send(PlaySFXEvent(SFX::Right));
+    }
+
+    let (pos, option) = options.items[options.selected];
+    dot_pos.translation.x = pos.x - 2.;
+    dot_pos.translation.y = pos.y - 1.;
+
+    if !keys.just_pressed(KeyCode::Space) { return; }
+
+    let level = route.level;
+    let rng = rng.at_level(level);
+    let bet = match option {
+        CasinoOption::Leave => {
+            sfx.send(PlaySFXEvent(SFX::Select));
+            route.advance(rng);
+            transition.set_if_neq(ScreenTransition::to(GameState::Dummy));
+            return;
+        }
+        CasinoOption::Slots => casino::SLOTS_BET,
+        CasinoOption::Crate(_) => casino::CRATE_BET,
+    };
+
+    if status.get_credits() < bet || (crates.1.is_some() && option != CasinoOption::Slots) {
+        sfx.send(PlaySFXEvent(SFX::Error));
+        return;
+    }
+    status.buy(bet);
+
+    let (result, prize) = match option {
+        CasinoOption::Crate(n) => {
+            crates.1 = Some(n);
+            (format!("Crate {}:", n + 1), Some(crates.0[n]))
+        }
+        _ => {
+            let reels: Vec<&(&str, u32, Effect)> = (0..3)
+                .map(|_| casino::SLOTS.choose_weighted(rng, |&(_, weight, _)| weight).unwrap())
+                .collect();
+            let (a, b, c) = (reels[0].0, reels[1].0, reels[2].0);
+            let prize = if a == b && b == c { Some(reels[0].2) }
+                else if a == b || b == c || a == c { Some(casino::SLOTS_PAIR) }
+                else { None };
+            (format!("[{} {} {}]", a, b, c), prize)
+        }
+    };
+
+    result_text.sections[0].value = match prize {
+        Some(prize) => {
+            sfx.send(PlaySFXEvent(SFX::Buy));
+            prize.apply(&mut status, &mut route, rng);
+            format!("{}\n{}", result, prize.text())
+        }
+        None => {
+            sfx.send(PlaySFXEvent(SFX::Cancel));
+            format!("{}\nNo luck.", result)
+        }
+    };
+    credits_text.sections[0].value = util::format_credits(status.get_credits());
+}
+
+fn enter(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    fonts: Res<Fonts>,
+    status: Res<ShipStatus>,
+    route: Res<CurrentRoute>,
+    mut star_field: ResMut<StarsSpeed>,
+    mut rng: ResMut<RunRng>,
+) {
+    star_field.set_by_level(route.loop_level());
+
+    let rng = rng.at_level(route.level);
+    let crates = (0..casino::CRATES)
+        .map(|_| casino::CRATE_CONTENTS.choose_weighted(rng, |&(weight, _)| weight).unwrap().1)
+        .collect();
+    commands.insert_resource(Crates(crates, None));
+
+    let mut options = vec![(vec2(16., 96.), CasinoOption::Slots)];
+    for n in 0..casino::CRATES {
+        options.push((vec2(16., 84. - 10. * n as f32), CasinoOption::Crate(n)));
+    }
+    options.push((vec2(16., 8.), CasinoOption::Leave));
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section("---CASINO---", TextStyles::Basic.style(&fonts)),
+            text_anchor: Anchor::BottomCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, HEIGHT as f32 - 12., z_pos::GUI),
+            ..default()
+        })
+        .insert(CasinoUI)
+    ;
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(util::format_credits(status.get_credits()), TextStyles::Basic.style(&fonts)),
+            text_anchor: Anchor::BottomCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, HEIGHT as f32 - 24., z_pos::GUI),
+            ..default()
+        })
+        .insert(CreditsText)
+        .insert(CasinoUI)
+    ;
+
+    for (pos, option) in options.iter() {
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(option.text(), TextStyles::Basic.style(&fonts)),
+                text_anchor: Anchor::BottomLeft,
+                transform: Transform::from_xyz(pos.x, pos.y - 4., z_pos::GUI),
+                ..default()
+            })
+            .insert(*option)
+            .insert(CasinoUI)
+        ;
+    }
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section("", TextStyles::Basic.style(&fonts))
+                .with_alignment(TextAlignment::Center)
+            ,
+            text_anchor: Anchor::Center,
+            transform: Transform::from_xyz(HALF_WIDTH, 36., z_pos::GUI),
+            ..default()
+        })
+        .insert(ResultText)
+        .insert(CasinoUI)
+    ;
+
+    commands
+        .spawn(SpriteBundle {
+            texture: textures.dot.clone(),
+            sprite: Sprite {
+                anchor: Anchor::BottomRight,
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., z_pos::GUI),
+            ..default()
+        })
+        .insert(SelectionDot)
+        .insert(CasinoUI)
+    ;
+
+    commands.insert_resource(Select { items: options, selected: 0 });
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
+            text_anchor: Anchor::BottomLeft,
+            transform: Transform::from_xyz(HALF_WIDTH + 24., 4., z_pos::GUI),
+            ..default()
+        })
+        .insert(CasinoUI)
+    ;
+}
+
+fn exit(
+    mut commands: Commands,
+    to_clean: Query<Entity, With<CasinoUI>>,
+) {
+    for id in to_clean.iter() {
+        commands
+            .entity(id)
+            .despawn_recursive();
+    }
+}
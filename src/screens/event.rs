@@ -0,0 +1,162 @@
+use bevy::app::App;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::GameState;
+use crate::graphics::{ScreenTransition, StarsSpeed, TextStyles};
+use crate::logic::event::{RouteEvent, RouteEvents};
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
+use crate::logic::ShipStatus;
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::{Data, Fonts, Textures};
+use crate::screens::map::map_closed;
+use crate::screens::shop::Select;
+use crate::screens::text::SimpleText;
+use crate::util::{HALF_WIDTH, HEIGHT, z_pos};
+
+pub struct EventPlugin;
+
+#[derive(Component)]
+struct EventUI;
+
+#[derive(Component)]
+struct SelectionDot;
+
+#[derive(Resource)]
+struct CurrentEvent(RouteEvent);
+
+impl Plugin for EventPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, update.run_if(in_state(GameState::Event).and_then(map_closed)))
+            .add_systems(OnEnter(GameState::Event), enter)
+            .add_systems(OnExit(GameState::Event), exit)
+        ;
+    }
+}
+
+fn update(
+    keys: Res<Input<KeyCode>>,
+    event: Res<CurrentEvent>,
+    mut route: ResMut<CurrentRoute>,
+    mut status: ResMut<ShipStatus>,
+    mut select: ResMut<Select<usize>>,
+    mut simple_text: ResMut<SimpleText>,
+    mut transition: ResMut<ScreenTransition>,
+    mut dot: Query<&mut Transform, With<SelectionDot>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+    mut rng: ResMut<RunRng>,
+) {
+    // Select previous / next choice
+    if keys.just_pressed(KeyCode::Up) {
+        select.selected = (select.items.len() + select.selected - 1) % select.items.len();
+        sfx.send(PlaySFXEvent(SFX::Left));
+    } else if keys.just_pressed(KeyCode::Down) {
+        select.selected = (select.selected + 1) % select.items.len();
+        sfx.send(PlaySFXEvent(SFX::Right));
+    }
+
+    let (pos, choice) = select.items[select.selected];
+
+    let Ok(mut dot_pos) = dot.get_single_mut() else { return; };
+    dot_pos.translation.x = pos.x - 2.;
+    dot_pos.translation.y = pos.y - 6.;
+
+    if transition.is_none() && keys.just_pressed(KeyCode::Space) {
+        let choice = &event.0.choices[choice];
+        if !choice.affordable(&status) {
+            sfx.send(PlaySFXEvent(SFX::Error));
+            return;
+        }
+
+        sfx.send(PlaySFXEvent(SFX::Select));
+        let rng = rng.at_level(route.level);
+        for effect in choice.effects.iter() { effect.apply(&mut status, &mut route, rng); }
+        simple_text.0 = choice.result.clone();
+        transition.set_if_neq(ScreenTransition::to(GameState::SimpleText));
+    }
+}
+
+fn enter(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    fonts: Res<Fonts>,
+    data: Res<Data>,
+    events: Res<Assets<RouteEvents>>,
+    status: Res<ShipStatus>,
+    mut star_field: ResMut<StarsSpeed>,
+    route: Res<CurrentRoute>,
+    mut rng: ResMut<RunRng>,
+) {
+    star_field.set_by_level(route.loop_level());
+
+    let event = events.get(&data.events).unwrap().random(rng.at_level(route.level)).clone();
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(&event.text, TextStyles::Basic.style(&fonts))
+                .with_alignment(TextAlignment::Center)
+            ,
+            text_anchor: Anchor::TopCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, HEIGHT as f32 - 16., z_pos::GUI),
+            ..default()
+        })
+        .insert(EventUI)
+    ;
+
+    let mut choices = vec![];
+    for (i, choice) in event.choices.iter().enumerate() {
+        let pos = vec2(16., 96. - 24. * i as f32);
+        let style = if choice.affordable(&status) { TextStyles::Basic } else { TextStyles::Gray };
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(&choice.text, style.style(&fonts)),
+                text_anchor: Anchor::TopLeft,
+                transform: Transform::from_xyz(pos.x, pos.y, z_pos::GUI),
+                ..default()
+            })
+            .insert(EventUI)
+        ;
+        choices.push((pos, i));
+    }
+
+    commands
+        .spawn(SpriteBundle {
+            texture: textures.dot.clone(),
+            sprite: Sprite {
+                anchor: Anchor::BottomRight,
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., z_pos::GUI),
+            ..default()
+        })
+        .insert(SelectionDot)
+        .insert(EventUI)
+    ;
+
+    commands.insert_resource(Select { items: choices, selected: 0 });
+    commands.insert_resource(CurrentEvent(event));
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
+            text_anchor: Anchor::BottomCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, 2., z_pos::GUI),
+            ..default()
+        })
+        .insert(EventUI)
+    ;
+}
+
+fn exit(
+    mut commands: Commands,
+    to_clean: Query<Entity, With<EventUI>>,
+) {
+    for id in to_clean.iter() {
+        commands
+            .entity(id)
+            .despawn_recursive();
+    }
+}
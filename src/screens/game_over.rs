@@ -2,13 +2,18 @@ use bevy::app::App;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
+use crate::entities::ShipRegistry;
 use crate::GameState;
 use crate::graphics::{ScreenTransition, StarsSpeed, TextStyles};
 use crate::logic::damage::KillCount;
-use crate::logic::route::{CurrentRoute, Route};
+use crate::logic::rng::RunRng;
+use crate::logic::daily::{Daily, DailyScore};
+use crate::logic::director::Director;
+use crate::logic::progress::Progress;
+use crate::logic::route::{CurrentRoute, GameMode};
 use crate::logic::ShipStatus;
 use crate::music::{PlaySFXEvent, SFX};
-use crate::screens::Fonts;
+use crate::screens::{Data, Fonts};
 use crate::util::{HALF_HEIGHT, HALF_WIDTH, z_pos};
 
 pub struct GameOverPlugin;
@@ -47,11 +52,17 @@ fn enter(
     mut ship_status: ResMut<ShipStatus>,
     mut stars_speed: ResMut<StarsSpeed>,
     route: Res<CurrentRoute>,
+    rng: Res<RunRng>,
+    daily: Option<Res<Daily>>,
     fonts: Res<Fonts>,
+    mut progress: ResMut<Progress>,
+    data: Res<Data>,
+    ships: Res<Assets<ShipRegistry>>,
 ) {
     stars_speed.set_by_level(0);
 
     commands.remove_resource::<KillCount>();
+    commands.remove_resource::<Director>();
 
     let mut texts = vec!(
         ScreenItem::Text(if route.win() { "Congratulations!" } else { "Game Over :(" }.to_string()),
@@ -66,6 +77,21 @@ fn enter(
 
     ship_status.non_stat_upgrades().iter().for_each(|u| texts.push(ScreenItem::Text(u.name().to_string())));
 
+    texts.push(ScreenItem::Space(8.));
+    if route.mode == GameMode::Endless {
+        texts.push(ScreenItem::Text(format!("Loops: {}", route.loops())));
+    }
+    // Practice runs don't unlock anything
+    let before = progress.acts_cleared;
+    let acts_cleared = route.acts_cleared();
+    if route.mode != GameMode::Practice && progress.record(acts_cleared) {
+        for module in ships.get(&data.ships).unwrap().modules() {
+            if module.unlock > before && module.unlock <= acts_cleared {
+                texts.push(ScreenItem::Text(format!("Unlocked: {}", module.name)));
+            }
+        }
+    }
+    texts.push(ScreenItem::Text(format!("Seed: {}", rng.seed())));
     texts.push(ScreenItem::Space(8.));
     texts.push(ScreenItem::Text("Press A to return".to_string()));
     texts.push(ScreenItem::Text("to the hangar.".to_string()));
@@ -102,7 +128,7 @@ fn enter(
     if !route.win() {
         commands
             .spawn(Text2dBundle {
-                text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * Route::act_len())), TextStyles::Basic.style(&fonts)),
+                text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
                 text_anchor: Anchor::BottomCenter,
                 transform: Transform::from_xyz(HALF_WIDTH, 2., z_pos::GUI),
                 ..default()
@@ -111,7 +137,14 @@ fn enter(
         ;
     }
 
+    if let Some(daily) = daily {
+        let level = route.level + 1 - (route.act() - 1) * route.act_len();
+        daily.save_score(DailyScore { win: route.win(), act: route.act(), level });
+    }
+
     commands.remove_resource::<CurrentRoute>();
+    commands.remove_resource::<RunRng>();
+    commands.remove_resource::<Daily>();
 }
 
 fn exit(
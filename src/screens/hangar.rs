@@ -3,13 +3,17 @@ use bevy::math::vec2;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
-use crate::entities::{MuteShots, Ship, Ships, Shot};
+use crate::entities::{MuteShots, Ship, ShipRegistry, Ships, ShipWeapons, Shot};
 use crate::GameState;
 use crate::graphics::{CurrentPalette, Palette, ScreenTransition, StarsSpeed, TextStyles};
-use crate::logic::route::{CurrentRoute, GameMode};
+use crate::logic::daily::Daily;
+use crate::logic::director::Director;
+use crate::logic::progress::Progress;
+use crate::logic::rng::RunRng;
+use crate::logic::route::{CurrentRoute, GameMode, RouteTemplate};
 use crate::logic::ShipBundle;
 use crate::music::{PlaySFXEvent, SFX};
-use crate::screens::{Fonts, Textures};
+use crate::screens::{Data, Fonts, Textures};
 use crate::util::{HALF_WIDTH, star_field, z_pos};
 
 pub struct HangarPlugin;
@@ -21,8 +25,13 @@ impl Plugin for HangarPlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(SelectedShip(PlayableShips::Ship1))
+            .insert_resource(SelectedModule(0))
+            .insert_resource(Controls::Classic)
+            .insert_resource(Adaptive(false))
+            .insert_resource(Progress::load())
+            .insert_resource(SelectedSeed(None))
             .add_event::<UpdateGUI>()
-            .add_systems(Update, (update, update_text, update_shooting)
+            .add_systems(Update, (update, update_text, update_shooting, update_seed, update_controls, update_adaptive)
                 .run_if(in_state(GameState::Hangar))
             )
             .add_systems(OnEnter(GameState::Hangar), enter)
@@ -34,6 +43,59 @@ impl Plugin for HangarPlugin {
 #[derive(Resource)]
 pub struct SelectedShip(pub PlayableShips);
 
+/// Index of the weapon module in the [ShipRegistry], chosen independently of the hull
+#[derive(Resource)]
+pub struct SelectedModule(pub usize);
+
+/// Control scheme of the main ship, switched in the hangar
+#[derive(Resource, Copy, Clone, Eq, PartialEq)]
+pub enum Controls {
+    /// Left and right only, Up fires a missile and Down raises the shield
+    Classic,
+    /// Arrows move the ship anywhere in the play area, missile and shield move to X and Z
+    Free,
+}
+
+impl Controls {
+    fn text(&self) -> &str {
+        match self {
+            Controls::Classic => "Controls: classic",
+            Controls::Free => "Controls: free",
+        }
+    }
+
+    pub fn missile_key(&self) -> KeyCode {
+        match self {
+            Controls::Classic => KeyCode::Up,
+            Controls::Free => KeyCode::X,
+        }
+    }
+
+    pub fn shield_key(&self) -> KeyCode {
+        match self {
+            Controls::Classic => KeyCode::Down,
+            Controls::Free => KeyCode::Z,
+        }
+    }
+}
+
+/// Whether the [Director] adapts the waves, switched in the hangar.
+/// Off by default so that a typed seed always gives the same run.
+#[derive(Resource)]
+struct Adaptive(bool);
+
+impl Adaptive {
+    fn text(&self) -> &str {
+        if self.0 { "Adaptive: on" } else { "Adaptive: off" }
+    }
+}
+
+/// Seed typed in the hangar, a random one is used if [None]
+#[derive(Resource)]
+struct SelectedSeed(Option<u64>);
+
+const SEED_MAX_DIGITS: usize = 10;
+
 #[derive(Component)]
 struct Legend;
 
@@ -46,6 +108,15 @@ struct ShipDescription(u8);
 #[derive(Component)]
 struct GameModeText(GameMode);
 
+#[derive(Component)]
+struct SeedText;
+
+#[derive(Component)]
+struct ControlsText;
+
+#[derive(Component)]
+struct AdaptiveText;
+
 #[derive(Event)]
 struct UpdateGUI;
 
@@ -67,12 +138,23 @@ impl PlayableShips {
         }
     }
 
-    fn description(&self) -> (&str, &str) {
+    /// Stats of the hull, the weapons come from the module
+    fn description(&self) -> &str {
         match self {
-            PlayableShips::Ship1 => ("Dual shots", "Balanced"),
-            PlayableShips::Ship2 => ("Large shots", "Health +"),
-            PlayableShips::Ship3 => ("Triple shots", "Speed +"),
-            PlayableShips::Ship4 => ("Laser", "Health -"),
+            PlayableShips::Ship1 => "Balanced",
+            PlayableShips::Ship2 => "Health +",
+            PlayableShips::Ship3 => "Speed +",
+            PlayableShips::Ship4 => "Health -",
+        }
+    }
+
+    /// Weapon module the hull comes with, always unlocked
+    pub(crate) fn module(&self) -> usize {
+        match self {
+            PlayableShips::Ship1 => 0,
+            PlayableShips::Ship2 => 1,
+            PlayableShips::Ship3 => 2,
+            PlayableShips::Ship4 => 3,
         }
     }
 
@@ -94,7 +176,7 @@ impl PlayableShips {
         }
     }
 
-    fn next(&self) -> Self {
+    pub(crate) fn next(&self) -> Self {
         match self {
             PlayableShips::Ship1 => PlayableShips::Ship2,
             PlayableShips::Ship2 => PlayableShips::Ship3,
@@ -117,10 +199,18 @@ fn update(
     mut commands: Commands,
     mut transition: ResMut<ScreenTransition>,
     mut selection: ResMut<SelectedShip>,
+    mut module: ResMut<SelectedModule>,
+    progress: Res<Progress>,
+    ships: Res<Assets<ShipRegistry>>,
     mut update_gui: EventWriter<UpdateGUI>,
     keys: Res<Input<KeyCode>>,
     mut sfx: EventWriter<PlaySFXEvent>,
-    mut mode: Query<(&mut Text, &mut GameModeText)>,
+    mut mode: Query<(&mut Text, &mut GameModeText), Without<SeedText>>,
+    mut seed_text: Query<&mut Text, (With<SeedText>, Without<GameModeText>)>,
+    seed: Res<SelectedSeed>,
+    adaptive: Res<Adaptive>,
+    data: Res<Data>,
+    templates: Res<Assets<RouteTemplate>>,
 ) {
     if !transition.is_none() { return; }
     let Ok((mut text, mut mode)) = mode.get_single_mut() else { return; };
@@ -129,6 +219,9 @@ fn update(
         sfx.send(PlaySFXEvent(SFX::Right));
         mode.0 = mode.0.next();
         text.sections[0].value = mode.0.text().to_string();
+        if let Ok(mut seed_text) = seed_text.get_single_mut() {
+            seed_text.sections[0].value = self::seed_text(&seed, mode.0);
+        }
     }
 
     if keys.just_pressed(KeyCode::Left) {
@@ -143,11 +236,42 @@ fn update(
         update_gui.send(UpdateGUI);
     }
 
+    // Only the unlocked modules are offered
+    let modules = ships.get(&data.ships).unwrap().modules();
+    if keys.just_pressed(KeyCode::Up) {
+        sfx.send(PlaySFXEvent(SFX::Left));
+        module.0 = progress.cycle(modules, module.0, -1);
+        update_gui.send(UpdateGUI);
+    }
+
+    if keys.just_pressed(KeyCode::Down) {
+        sfx.send(PlaySFXEvent(SFX::Right));
+        module.0 = progress.cycle(modules, module.0, 1);
+        update_gui.send(UpdateGUI);
+    }
+
     if keys.just_pressed(KeyCode::Space) {
         sfx.send(PlaySFXEvent(SFX::Select));
-        let route = CurrentRoute::new(mode.0);
-        transition.set_if_neq(ScreenTransition::to(route.state()));
+        let mut rng = if mode.0 == GameMode::Daily {
+            // The daily challenge imposes its seed and ship, with the module of the ship
+            let daily = Daily::today();
+            selection.0 = daily.ship();
+            module.0 = daily.ship().module();
+            update_gui.send(UpdateGUI);
+            commands.insert_resource(daily);
+            RunRng::new(daily.seed())
+        } else {
+            commands.remove_resource::<Daily>();
+            RunRng::new(seed.0.unwrap_or_else(RunRng::random_seed))
+        };
+        if adaptive.0 && mode.0.adaptive() { commands.insert_resource(Director::default()); }
+        else { commands.remove_resource::<Director>(); }
+        let template = templates.get(&data.route).unwrap();
+        let route = CurrentRoute::new(mode.0, template, &mut rng);
+        let state = if mode.0 == GameMode::Practice { GameState::Practice } else { route.state() };
+        transition.set_if_neq(ScreenTransition::to(state));
         commands.insert_resource(route);
+        commands.insert_resource(rng);
     }
 }
 
@@ -157,7 +281,17 @@ fn enter(
     mut update_gui: EventWriter<UpdateGUI>,
     textures: Res<Textures>,
     fonts: Res<Fonts>,
+    seed: Res<SelectedSeed>,
+    selection: Res<SelectedShip>,
+    mut module: ResMut<SelectedModule>,
+    progress: Res<Progress>,
+    controls: Res<Controls>,
+    adaptive: Res<Adaptive>,
+    data: Res<Data>,
+    ships: Res<Assets<ShipRegistry>>,
 ) {
+    let ships = ships.get(&data.ships).unwrap();
+    if !ships.modules().get(module.0).is_some_and(|selected| progress.unlocked(selected)) { module.0 = selection.0.module(); }
     star_speed.0 = star_field::HANGAR_SPEED;
     update_gui.send(UpdateGUI);
 
@@ -181,7 +315,7 @@ fn enter(
         (PlayableShips::Ship4, 44. + 24. * 3.),
     ] {
         commands
-            .spawn(ShipBundle::from(textures.ship.clone(), ship.model(), vec2(x, 50.)))
+            .spawn(ShipBundle::from(textures.ship.clone(), ships, ship.model(), vec2(x, 50.)))
             .insert(HangarUI)
         ;
     }
@@ -243,10 +377,131 @@ fn enter(
         .insert(GameModeText(GameMode::Standard))
         .insert(HangarUI)
     ;
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(seed_text(&seed, GameMode::Standard), TextStyles::Gray.style(&fonts)),
+            text_anchor: Anchor::BottomCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, 114. - 4. - 9., z_pos::HANGAR_TEXT),
+            ..default()
+        })
+        .insert(SeedText)
+        .insert(HangarUI)
+    ;
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(controls.text(), TextStyles::Gray.style(&fonts)),
+            text_anchor: Anchor::BottomCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, 114. - 4. - 18., z_pos::HANGAR_TEXT),
+            ..default()
+        })
+        .insert(ControlsText)
+        .insert(HangarUI)
+    ;
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(adaptive.text(), TextStyles::Gray.style(&fonts)),
+            text_anchor: Anchor::BottomCenter,
+            transform: Transform::from_xyz(HALF_WIDTH, 114. - 4. - 27., z_pos::HANGAR_TEXT),
+            ..default()
+        })
+        .insert(AdaptiveText)
+        .insert(HangarUI)
+    ;
+}
+
+fn seed_text(seed: &SelectedSeed, mode: GameMode) -> String {
+    if mode == GameMode::Daily {
+        return match Daily::today().score() {
+            Some(score) if score.win => "Today: won!".to_string(),
+            Some(score) => format!("Today: reached {}-{}", score.act, score.level),
+            None => "Today: not played yet".to_string(),
+        };
+    }
+    match seed.0 {
+        Some(s) => format!("Seed: {}", s),
+        None => "Seed: random".to_string(),
+    }
+}
+
+/// Type a seed with the digit keys, backspace to erase
+fn update_seed(
+    keys: Res<Input<KeyCode>>,
+    transition: Res<ScreenTransition>,
+    mut seed: ResMut<SelectedSeed>,
+    mut text: Query<&mut Text, With<SeedText>>,
+    mode: Query<&GameModeText>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    if !transition.is_none() { return; }
+    let Ok(mut text) = text.get_single_mut() else { return; };
+    let Ok(mode) = mode.get_single() else { return; };
+    if mode.0 == GameMode::Daily { return; }
+
+    let digits = [
+        (KeyCode::Key0, KeyCode::Numpad0), (KeyCode::Key1, KeyCode::Numpad1),
+        (KeyCode::Key2, KeyCode::Numpad2), (KeyCode::Key3, KeyCode::Numpad3),
+        (KeyCode::Key4, KeyCode::Numpad4), (KeyCode::Key5, KeyCode::Numpad5),
+        (KeyCode::Key6, KeyCode::Numpad6), (KeyCode::Key7, KeyCode::Numpad7),
+        (KeyCode::Key8, KeyCode::Numpad8), (KeyCode::Key9, KeyCode::Numpad9),
+    ];
+
+    if let Some(digit) = digits.iter().position(|(k, n)| keys.just_pressed(*k) || keys.just_pressed(*n)) {
+        let current = seed.0.unwrap_or(0);
+        if current.to_string().len() < SEED_MAX_DIGITS || seed.0.is_none() {
+            seed.0 = Some(current * 10 + digit as u64);
+            sfx.send(PlaySFXEvent(SFX::Right));
+        }
+    } else if keys.just_pressed(KeyCode::Back) {
+        seed.0 = seed.0.and_then(|s| if s >= 10 { Some(s / 10) } else { None });
+        sfx.send(PlaySFXEvent(SFX::Left));
+    } else {
+        return;
+    }
+
+    text.sections[0].value = seed_text(&seed, mode.0);
+}
+
+/// Switch the control scheme with C
+fn update_controls(
+    keys: Res<Input<KeyCode>>,
+    transition: Res<ScreenTransition>,
+    mut controls: ResMut<Controls>,
+    mut text: Query<&mut Text, With<ControlsText>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    if !transition.is_none() || !keys.just_pressed(KeyCode::C) { return; }
+    let Ok(mut text) = text.get_single_mut() else { return; };
+    *controls = match *controls {
+        Controls::Classic => Controls::Free,
+        Controls::Free => Controls::Classic,
+    };
+    text.sections[0].value = controls.text().to_string();
+    sfx.send(PlaySFXEvent(SFX::Right));
+}
+
+/// Switch the adaptive difficulty with D
+fn update_adaptive(
+    keys: Res<Input<KeyCode>>,
+    transition: Res<ScreenTransition>,
+    mut adaptive: ResMut<Adaptive>,
+    mut text: Query<&mut Text, With<AdaptiveText>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    if !transition.is_none() || !keys.just_pressed(KeyCode::D) { return; }
+    let Ok(mut text) = text.get_single_mut() else { return; };
+    adaptive.0 = !adaptive.0;
+    text.sections[0].value = adaptive.text().to_string();
+    sfx.send(PlaySFXEvent(SFX::Right));
 }
 
 fn update_text(
     selected_ship: Res<SelectedShip>,
+    module: Res<SelectedModule>,
+    data: Res<Data>,
+    ships: Res<Assets<ShipRegistry>>,
     mut ev: EventReader<UpdateGUI>,
     mut palette: ResMut<CurrentPalette>,
     mut legend: Query<(&mut Sprite, &mut Transform), (With<Legend>, Without<ShipName>, Without<ShipDescription>)>,
@@ -287,8 +542,9 @@ fn update_text(
     *text_anchor = anchor.clone();
     text_pos.translation.x = x;
 
-    // Update description
-    let (line_1, line_2) = ship.description();
+    // Update description, weapons then hull
+    let line_1 = &ships.get(&data.ships).unwrap().module(module.0).name;
+    let line_2 = ship.description();
     for (mut description_text, info, mut text_anchor, mut text_pos) in description.iter_mut() {
         match info.0 {
             1 => description_text.sections[0].value = line_1.to_string(),
@@ -300,18 +556,27 @@ fn update_text(
     }
 }
 
+/// Only the selected ship shoots, with the selected module
 fn update_shooting(
     mut commands: Commands,
     selected_ship: Res<SelectedShip>,
+    module: Res<SelectedModule>,
+    data: Res<Data>,
+    registry: Res<Assets<ShipRegistry>>,
     mut ev: EventReader<UpdateGUI>,
-    ships: Query<(Entity, &Ship)>
+    mut ships: Query<(Entity, &Ship, &mut ShipWeapons)>
 ) {
     if ev.is_empty() { return; }
     ev.clear();
 
-    for (e, ship) in &ships {
-        if ship.model == selected_ship.0.model() { commands.entity(e).remove::<MuteShots>(); }
-        else { commands.entity(e).insert(MuteShots); }
+    let module = registry.get(&data.ships).unwrap().module(module.0);
+    for (e, ship, mut weapons) in ships.iter_mut() {
+        if ship.model == selected_ship.0.model() {
+            *weapons = ShipWeapons::new(ship, module.weapons.clone());
+            commands.entity(e).remove::<MuteShots>();
+        } else {
+            commands.entity(e).insert(MuteShots);
+        }
     }
 }
 
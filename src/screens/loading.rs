@@ -1,7 +1,12 @@
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
 
+use crate::entities::{BulletPatterns, ShipRegistry};
 use crate::GameState;
+use crate::logic::WaveTemplates;
+use crate::logic::event::RouteEvents;
+use crate::logic::route::RouteTemplate;
 use crate::music::{BGM, PlayBGMEvent};
 
 pub struct LoadingPlugin;
@@ -9,6 +14,13 @@ pub struct LoadingPlugin;
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_plugins((
+                RonAssetPlugin::<RouteTemplate>::new(&["route.ron"]),
+                RonAssetPlugin::<RouteEvents>::new(&["events.ron"]),
+                RonAssetPlugin::<WaveTemplates>::new(&["waves.ron"]),
+                RonAssetPlugin::<BulletPatterns>::new(&["patterns.ron"]),
+                RonAssetPlugin::<ShipRegistry>::new(&["ships.ron"]),
+            ))
             .add_loading_state(
                 LoadingState::new(GameState::Loading)
                     .continue_to_state(GameState::Title),
@@ -16,12 +28,22 @@ impl Plugin for LoadingPlugin {
             .add_collection_to_loading_state::<_, Textures>(GameState::Loading)
             .add_collection_to_loading_state::<_, Fonts>(GameState::Loading)
             .add_collection_to_loading_state::<_, Sounds>(GameState::Loading)
+            .add_collection_to_loading_state::<_, Data>(GameState::Loading)
             .add_systems(OnExit(GameState::Loading), exit)
         ;
     }
 }
 
-fn exit(mut play_bgm: EventWriter<PlayBGMEvent>) { play_bgm.send(PlayBGMEvent(BGM::Title)); }
+fn exit(
+    mut play_bgm: EventWriter<PlayBGMEvent>,
+    data: Res<Data>,
+    patterns: Res<Assets<BulletPatterns>>,
+    ships: Res<Assets<ShipRegistry>>,
+) {
+    let (patterns, ships) = (patterns.get(&data.patterns).unwrap(), ships.get(&data.ships).unwrap());
+    if let Err(e) = patterns.check_ships(ships) { panic!("data/enemies.patterns.ron: {}", e); }
+    play_bgm.send(PlayBGMEvent(BGM::Title));
+}
 
 #[derive(AssetCollection, Resource)]
 pub struct Textures {
@@ -137,4 +159,23 @@ pub struct Sounds {
 
     #[asset(path = "sfx/OS_Leech.ogg")]
     pub leech: Handle<AudioSource>,
-}
\ No newline at end of file
+}
+
+/// Game data tuned from RON files
+#[derive(AssetCollection, Resource)]
+pub struct Data {
+    #[asset(path = "data/acts.route.ron")]
+    pub route: Handle<RouteTemplate>,
+
+    #[asset(path = "data/route.events.ron")]
+    pub events: Handle<RouteEvents>,
+
+    #[asset(path = "data/space.waves.ron")]
+    pub waves: Handle<WaveTemplates>,
+
+    #[asset(path = "data/enemies.patterns.ron")]
+    pub patterns: Handle<BulletPatterns>,
+
+    #[asset(path = "data/fleet.ships.ron")]
+    pub ships: Handle<ShipRegistry>,
+}
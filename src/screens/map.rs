@@ -0,0 +1,164 @@
+use bevy::app::App;
+use bevy::math::vec2;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::GameState;
+use crate::graphics::{FakeTransform, Palette, TextStyles};
+use crate::logic::route::CurrentRoute;
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::Fonts;
+use crate::util::{HEIGHT, in_states, map, WIDTH, z_pos};
+
+/// Overview of the current act, opened with Select while paused or between levels.
+pub struct MapPlugin;
+
+#[derive(Component)]
+pub struct MapUI;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, toggle
+                .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss, GameState::Shop, GameState::Upgrade, GameState::Event, GameState::Casino]))
+            )
+            .add_systems(OnExit(GameState::Space), close)
+            .add_systems(OnExit(GameState::Elite), close)
+            .add_systems(OnExit(GameState::Boss), close)
+            .add_systems(OnExit(GameState::Shop), close)
+            .add_systems(OnExit(GameState::Upgrade), close)
+            .add_systems(OnExit(GameState::Event), close)
+            .add_systems(OnExit(GameState::Casino), close)
+        ;
+    }
+}
+
+/// Run condition for screens that should ignore inputs while the map is open
+pub fn map_closed(map: Query<(), With<MapUI>>) -> bool { map.is_empty() }
+
+fn toggle(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    route: Res<CurrentRoute>,
+    fonts: Res<Fonts>,
+    map: Query<Entity, With<MapUI>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+) {
+    let in_fight = [GameState::Space, GameState::Elite, GameState::Boss].contains(state.get());
+    let open = !map.is_empty();
+
+    // The map can only be opened from the pause screen during fights
+    if in_fight && !time.is_paused() {
+        if open { map.for_each(|e| commands.entity(e).despawn_recursive()); }
+        return;
+    }
+
+    if !keys.just_pressed(KeyCode::S) { return; }
+
+    if open {
+        sfx.send(PlaySFXEvent(SFX::Cancel));
+        map.for_each(|e| commands.entity(e).despawn_recursive());
+    } else {
+        sfx.send(PlaySFXEvent(SFX::Select));
+        spawn_map(&mut commands, &route, &fonts);
+    }
+}
+
+fn node_pos(column: usize, node: usize, width: usize) -> Vec2 {
+    vec2(
+        (WIDTH as f32 * (node as f32 + 0.5) / width as f32).round(),
+        map::FIRST_ROW_Y + column as f32 * map::ROW_HEIGHT,
+    )
+}
+
+fn spawn_map(
+    commands: &mut Commands,
+    route: &CurrentRoute,
+    fonts: &Fonts,
+) {
+    let act_start = (route.act() - 1) * route.act_len();
+    let act_end = (act_start + route.act_len()).min(route.route.0.len());
+    let columns = &route.route.0[act_start..act_end];
+
+    // Hide the current screen
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                color: Palette::Greyscale.colors()[0],
+                custom_size: Some(vec2(WIDTH as f32, HEIGHT as f32)),
+                anchor: Anchor::BottomLeft,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(FakeTransform::from_xyz(0., 0., z_pos::MAP))
+        .insert(MapUI)
+    ;
+
+    commands
+        .spawn(Text2dBundle {
+            text: Text::from_section(format!("Act {}", route.act()), TextStyles::Basic.style(fonts)),
+            text_anchor: Anchor::TopLeft,
+            ..default()
+        })
+        .insert(FakeTransform::from_xyz(4., HEIGHT as f32 - 2., z_pos::MAP_TEXT))
+        .insert(MapUI)
+    ;
+
+    for (i, column) in columns.iter().enumerate() {
+        let level = act_start + i;
+        for (n, node) in column.iter().enumerate() {
+            let pos = node_pos(i, n, column.len());
+            let style = if level == route.level && n == route.node { TextStyles::Accent }
+                else if route.is_visited(level, n) { TextStyles::Basic }
+                else { TextStyles::Gray };
+
+            commands
+                .spawn(Text2dBundle {
+                    text: Text::from_section(node.level.short_name(), style.style(fonts)),
+                    text_anchor: Anchor::Center,
+                    ..default()
+                })
+                .insert(FakeTransform::from_xyz(pos.x, pos.y, z_pos::MAP_TEXT))
+                .insert(MapUI)
+            ;
+
+            // Dotted paths to the next column of the act
+            let Some(next_column) = columns.get(i + 1) else { continue };
+            for &next in node.next.iter() {
+                let next_pos = node_pos(i + 1, next, next_column.len());
+                let taken = route.is_visited(level, n) && route.is_visited(level + 1, next);
+                let (from, to) = (pos + vec2(0., 4.), next_pos - vec2(0., 4.));
+                for k in 1..=map::EDGE_DOTS {
+                    let dot = from.lerp(to, k as f32 / (map::EDGE_DOTS + 1) as f32);
+                    commands
+                        .spawn(SpriteBundle {
+                            sprite: Sprite {
+                                color: Palette::Greyscale.colors()[if taken { 2 } else { 3 }],
+                                custom_size: Some(vec2(1., 1.)),
+                                anchor: Anchor::BottomLeft,
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .insert(FakeTransform::from_xyz(dot.x, dot.y, z_pos::MAP_TEXT))
+                        .insert(MapUI)
+                    ;
+                }
+            }
+        }
+    }
+}
+
+fn close(
+    mut commands: Commands,
+    to_clean: Query<Entity, With<MapUI>>,
+) {
+    for id in to_clean.iter() {
+        commands
+            .entity(id)
+            .despawn_recursive();
+    }
+}
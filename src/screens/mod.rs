@@ -1,14 +1,20 @@
 use bevy::app::App;
 use bevy::prelude::*;
 
+pub use hangar::PlayableShips;
 pub use hangar::SelectedShip;
+pub use loading::Data;
 pub use loading::Fonts;
 pub use loading::Sounds;
 pub use loading::Textures;
 
+use crate::screens::casino::CasinoPlugin;
+use crate::screens::event::EventPlugin;
 use crate::screens::game_over::GameOverPlugin;
 use crate::screens::hangar::HangarPlugin;
 use crate::screens::loading::LoadingPlugin;
+use crate::screens::map::MapPlugin;
+use crate::screens::practice::PracticePlugin;
 use crate::screens::shop::ShopPlugin;
 use crate::screens::space::SpacePlugin;
 use crate::screens::text::SimpleTextPlugin;
@@ -23,6 +29,10 @@ mod shop;
 mod upgrade;
 mod text;
 mod game_over;
+mod map;
+mod event;
+mod practice;
+mod casino;
 
 pub struct ScreensPlugin;
 
@@ -38,6 +48,10 @@ impl Plugin for ScreensPlugin {
                 UpgradePlugin,
                 SimpleTextPlugin,
                 GameOverPlugin,
+                MapPlugin,
+                EventPlugin,
+                PracticePlugin,
+                CasinoPlugin,
             ))
         ;
     }
@@ -0,0 +1,186 @@
+use bevy::app::App;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::entities::ShipRegistry;
+use crate::GameState;
+use crate::graphics::{ScreenTransition, StarsSpeed, TextStyles};
+use crate::logic::practice::{Practice, PracticeFight};
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
+use crate::music::{PlaySFXEvent, SFX};
+use crate::screens::{Data, Fonts, SelectedShip, Textures};
+use crate::util::{HEIGHT, star_field, z_pos};
+
+/// Pick a fight and a loadout, losing or winning the fight comes back here.
+pub struct PracticePlugin;
+
+#[derive(Component)]
+struct PracticeUI;
+
+#[derive(Component)]
+struct SelectionDot;
+
+/// Text of a row of the practice screen
+#[derive(Component)]
+struct PracticeRow(usize);
+
+#[derive(Resource)]
+struct SelectedRow(usize);
+
+const ROW_HEIGHT: f32 = 8.;
+const MAX_HULL: usize = 40;
+const MAX_ITEMS: usize = 9;
+const MAX_STAT_UPGRADES: usize = 5;
+
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(SelectedRow(0))
+            .add_systems(Update, update.run_if(in_state(GameState::Practice)))
+            .add_systems(OnEnter(GameState::Practice), enter)
+            .add_systems(OnExit(GameState::Practice), exit)
+        ;
+    }
+}
+
+/// Rows are: fight, hull, missiles, shields, then one row per upgrade
+fn rows(practice: &Practice) -> usize { 4 + practice.upgrades.len() }
+
+fn row_text(practice: &Practice, row: usize) -> String {
+    match row {
+        0 => format!("Fight: {}", practice.fight().name()),
+        1 => format!("Hull: {}", practice.hull),
+        2 => format!("Missiles: {}", practice.missiles),
+        3 => format!("Shields: {}", practice.shields),
+        _ => {
+            let (upgrade, n) = practice.upgrades[row - 4];
+            if upgrade.is_stat_upgrade() { format!("{} x{}", upgrade.name(), n) }
+            else { format!("{}: {}", upgrade.name(), if n > 0 { "on" } else { "off" }) }
+        }
+    }
+}
+
+/// Change the value of [row] by [delta], wrapping around
+fn change(practice: &mut Practice, row: usize, delta: isize) {
+    let step = |value: usize, min: usize, max: usize| -> usize {
+        let len = (max - min + 1) as isize;
+        (min as isize + (value as isize - min as isize + delta).rem_euclid(len)) as usize
+    };
+    match row {
+        0 => practice.fight = step(practice.fight, 0, PracticeFight::ALL.len() - 1),
+        1 => practice.hull = step(practice.hull, 1, MAX_HULL),
+        2 => practice.missiles = step(practice.missiles, 0, MAX_ITEMS),
+        3 => practice.shields = step(practice.shields, 0, MAX_ITEMS),
+        _ => {
+            let (upgrade, n) = &mut practice.upgrades[row - 4];
+            *n = step(*n, 0, if upgrade.is_stat_upgrade() { MAX_STAT_UPGRADES } else { 1 });
+        }
+    }
+}
+
+fn update(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut practice: ResMut<Practice>,
+    mut selected: ResMut<SelectedRow>,
+    mut route: ResMut<CurrentRoute>,
+    selected_ship: Res<SelectedShip>,
+    mut transition: ResMut<ScreenTransition>,
+    mut texts: Query<(&mut Text, &PracticeRow)>,
+    mut dot: Query<&mut Transform, With<SelectionDot>>,
+    mut sfx: EventWriter<PlaySFXEvent>,
+    data: Res<Data>,
+    ships: Res<Assets<ShipRegistry>>,
+) {
+    if !transition.is_none() { return; }
+
+    let rows = rows(&practice);
+    if keys.just_pressed(KeyCode::Up) {
+        selected.0 = (rows + selected.0 - 1) % rows;
+        sfx.send(PlaySFXEvent(SFX::Left));
+    } else if keys.just_pressed(KeyCode::Down) {
+        selected.0 = (selected.0 + 1) % rows;
+        sfx.send(PlaySFXEvent(SFX::Right));
+    } else if keys.just_pressed(KeyCode::Left) {
+        change(&mut practice, selected.0, -1);
+        sfx.send(PlaySFXEvent(SFX::Left));
+    } else if keys.just_pressed(KeyCode::Right) {
+        change(&mut practice, selected.0, 1);
+        sfx.send(PlaySFXEvent(SFX::Right));
+    }
+
+    if practice.is_changed() {
+        for (mut text, row) in texts.iter_mut() {
+            text.sections[0].value = row_text(&practice, row.0);
+        }
+    }
+
+    if let Ok(mut dot_pos) = dot.get_single_mut() {
+        dot_pos.translation.y = row_y(selected.0) - 4.;
+    }
+
+    if keys.just_pressed(KeyCode::Space) {
+        sfx.send(PlaySFXEvent(SFX::Select));
+        let fight = practice.fight();
+        commands.insert_resource(practice.ship_status(&ships.get(&data.ships).unwrap().ship(selected_ship.0.model())));
+        let act_len = route.act_len();
+        route.jump(fight.level(act_len), fight.state());
+        transition.set_if_neq(ScreenTransition::to(fight.state()));
+    } else if keys.just_pressed(KeyCode::Escape) {
+        sfx.send(PlaySFXEvent(SFX::Cancel));
+        commands.remove_resource::<CurrentRoute>();
+        commands.remove_resource::<RunRng>();
+        transition.set_if_neq(ScreenTransition::to(GameState::Hangar));
+    }
+}
+
+fn row_y(row: usize) -> f32 { HEIGHT as f32 - 4. - row as f32 * ROW_HEIGHT }
+
+fn enter(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    fonts: Res<Fonts>,
+    practice: Res<Practice>,
+    mut star_speed: ResMut<StarsSpeed>,
+) {
+    star_speed.0 = star_field::HANGAR_SPEED;
+
+    for row in 0..rows(&practice) {
+        commands
+            .spawn(Text2dBundle {
+                text: Text::from_section(row_text(&practice, row), TextStyles::Basic.style(&fonts)),
+                text_anchor: Anchor::TopLeft,
+                transform: Transform::from_xyz(12., row_y(row), z_pos::GUI),
+                ..default()
+            })
+            .insert(PracticeRow(row))
+            .insert(PracticeUI)
+        ;
+    }
+
+    commands
+        .spawn(SpriteBundle {
+            texture: textures.dot.clone(),
+            sprite: Sprite {
+                anchor: Anchor::BottomRight,
+                ..default()
+            },
+            transform: Transform::from_xyz(10., 0., z_pos::GUI),
+            ..default()
+        })
+        .insert(SelectionDot)
+        .insert(PracticeUI)
+    ;
+}
+
+fn exit(
+    mut commands: Commands,
+    to_clean: Query<Entity, With<PracticeUI>>,
+) {
+    for id in to_clean.iter() {
+        commands
+            .entity(id)
+            .despawn_recursive();
+    }
+}
@@ -2,15 +2,17 @@ use bevy::app::App;
 use bevy::math::vec2;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
-use rand::{RngCore, thread_rng};
+use rand::RngCore;
 
 use crate::{GameState, util};
 use crate::graphics::{ScreenTransition, StarsSpeed, TextStyles};
 use crate::logic::{Items, ShipStatus};
-use crate::logic::route::{CurrentRoute, Route};
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
 use crate::logic::upgrades::Upgrades;
 use crate::music::{PlaySFXEvent, SFX};
 use crate::screens::{Fonts, Textures};
+use crate::screens::map::map_closed;
 use crate::screens::text::SimpleText;
 use crate::util::{shop, z_pos};
 
@@ -22,7 +24,7 @@ struct ShopUI;
 impl Plugin for ShopPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, update.run_if(in_state(GameState::Shop)))
+            .add_systems(Update, update.run_if(in_state(GameState::Shop).and_then(map_closed)))
             .add_systems(OnEnter(GameState::Shop), enter)
             .add_systems(OnExit(GameState::Shop), exit)
         ;
@@ -41,6 +43,7 @@ fn update(
     fonts: Res<Fonts>,
     mut simple_text: ResMut<SimpleText>,
     mut sfx: EventWriter<PlaySFXEvent>,
+    mut rng: ResMut<RunRng>,
 ) {
     if !transition.is_none() { return; }
 
@@ -92,7 +95,7 @@ fn update(
             }
             ShopOption::Exit => {
                 sfx.send(PlaySFXEvent(SFX::Select));
-                route.advance();
+                route.advance(&mut rng);
                 transition.set_if_neq(ScreenTransition::to(GameState::Dummy));
             }
         }
@@ -163,19 +166,20 @@ fn enter(
     ship_status: Res<ShipStatus>,
     route: Res<CurrentRoute>,
     mut star_field: ResMut<StarsSpeed>,
+    mut rng: ResMut<RunRng>,
 ) {
-    star_field.set_by_level(route.level);
+    star_field.set_by_level(route.loop_level());
 
-    let mut rng = thread_rng();
-    let mut is_sale = || rng.next_u32() % 10 == 0;
+    let rng = rng.at_level(route.level);
+    let is_sale = |rng: &mut RunRng| rng.next_u32() % 10 == 0;
 
     // Generate shop options
     let options = vec![
-        (vec2(32., 89.), ShopOption::Buy(Items::Upgrade(Upgrades::new_upgrade(&ship_status)), is_sale())),
-        (vec2(32., 77.), ShopOption::Buy(Items::Missile, is_sale())),
-        (vec2(32., 65.), ShopOption::Buy(Items::Shield, is_sale())),
-        (vec2(32., 53.), ShopOption::Buy(Items::Repair, is_sale())),
-        (vec2(32., 28.), ShopOption::Sell(Items::random_collectible())),
+        (vec2(32., 89.), ShopOption::Buy(Items::Upgrade(Upgrades::new_upgrade(&ship_status, rng)), is_sale(rng))),
+        (vec2(32., 77.), ShopOption::Buy(Items::Missile, is_sale(rng))),
+        (vec2(32., 65.), ShopOption::Buy(Items::Shield, is_sale(rng))),
+        (vec2(32., 53.), ShopOption::Buy(Items::Repair, is_sale(rng))),
+        (vec2(32., 28.), ShopOption::Sell(Items::random_collectible(rng))),
         (vec2(20., 8.), ShopOption::Exit),
     ];
 
@@ -195,7 +199,7 @@ fn enter(
 
     commands
         .spawn(Text2dBundle {
-            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * Route::act_len())), TextStyles::Basic.style(&fonts)),
+            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
             text_anchor: Anchor::BottomLeft,
             transform: Transform::from_xyz(56., 4., z_pos::GUI),
             ..default()
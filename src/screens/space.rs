@@ -1,19 +1,23 @@
 use bevy::app::App;
-use bevy::math::{vec2, Vec3Swizzles};
+use bevy::math::vec2;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
 
 use crate::{GameState, util};
-use crate::entities::{Blink, MainShip, MuteShots, Ship, Shot, Shots, Weapon};
+use crate::entities::{Blink, Charge, Homing, HomingShot, MainShip, MuteShots, Ship, ShipRegistry, ShipWeapons, Shot, Shots, Targeting, Weapon};
 use crate::graphics::{FakeTransform, Palette, ScreenTransition, StarsSpeed, TextStyles};
 use crate::graphics::sizes::Hitbox;
-use crate::logic::{Items, ShipBundle, ShipStatus, WaveCleared};
+use crate::logic::{BossPhases, Formation, Items, ShipBundle, ShipStatus, WaveCleared};
+use crate::logic::affix::Affixes;
+use crate::logic::boss;
+use crate::logic::boss::BossParts;
 use crate::logic::damage::{DamageEvent, KillCount};
-use crate::logic::route::{CurrentRoute, Level, Route, RouteElement};
+use crate::logic::rng::RunRng;
+use crate::logic::route::{CurrentRoute, GameMode};
 use crate::logic::upgrades::{ShotUpgrades, Upgrades};
 use crate::music::{PlaySFXEvent, SFX};
-use crate::screens::{Fonts, Textures};
-use crate::screens::hangar::SelectedShip;
+use crate::screens::{Data, Fonts, Textures};
+use crate::screens::hangar::{Controls, SelectedModule, SelectedShip};
 use crate::screens::text::SimpleText;
 use crate::util::{Angle, base_stats, BORDER, HALF_HEIGHT, HALF_WIDTH, HEIGHT, in_states, space, star_field, WIDTH, z_pos};
 use crate::util::hud::HEALTH_BAR_SIZE;
@@ -26,9 +30,15 @@ struct SpaceUI;
 #[derive(Component)]
 struct LifeBar;
 
+#[derive(Component)]
+struct ChargeBar;
+
 #[derive(Component)]
 struct EliteLifeBar;
 
+#[derive(Component)]
+struct EliteLabel;
+
 #[derive(Component)]
 struct CreditsText;
 
@@ -41,7 +51,7 @@ struct ItemsText;
 impl Plugin for SpacePlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, (update, update_gui, update_life, on_cleared, update_next, update_shield, update_missiles)
+            .add_systems(Update, (update, update_gui, update_life, update_charge, add_phase_markers, show_affixes, on_cleared, update_next, update_shield, update_missiles)
                 .run_if(in_states(vec![GameState::Space, GameState::Elite, GameState::Boss])),
             )
             .add_systems(PostUpdate, pause
@@ -61,6 +71,7 @@ impl Plugin for SpacePlugin {
 fn update(
     keys: Res<Input<KeyCode>>,
     time: Res<Time>,
+    controls: Res<Controls>,
     mut ship: Query<(&Ship, &Hitbox, &mut FakeTransform), Without<Rush>>,
 ) {
     for (s, hitbox, mut pos) in ship.iter_mut() {
@@ -75,12 +86,24 @@ fn update(
         if keys.pressed(KeyCode::Right) {
             if pos.translation.x + dx <= WIDTH as f32 { pos.translation.x += movement_x; }
         }
+
+        // Up and Down are kept for the missile and shield with the classic controls
+        if *controls != Controls::Free { continue; }
+        let movement_y = s.speed * time.delta_seconds();
+        let dy = movement_y + hitbox.0.y / 2. + BORDER;
+        if keys.pressed(KeyCode::Down) {
+            if pos.translation.y - dy >= space::PLAY_AREA_BOTTOM { pos.translation.y -= movement_y; }
+        }
+        if keys.pressed(KeyCode::Up) {
+            if pos.translation.y + dy <= space::PLAY_AREA_TOP { pos.translation.y += movement_y; }
+        }
     }
 }
 
 fn enter(
     mut commands: Commands,
     selected_ship: Res<SelectedShip>,
+    selected_module: Res<SelectedModule>,
     ship_status: Res<ShipStatus>,
     textures: Res<Textures>,
     fonts: Res<Fonts>,
@@ -89,13 +112,17 @@ fn enter(
     mut time: ResMut<Time>,
     state: Res<State<GameState>>,
     kill_count: Option<Res<KillCount>>,
+    data: Res<Data>,
+    ships: Res<Assets<ShipRegistry>>,
 ) {
-    stars_speed.set_by_level(route.level);
+    stars_speed.set_by_level(route.loop_level());
 
     if kill_count.is_none() && ship_status.has_upgrade(Upgrades::LeechShots) { commands.insert_resource(KillCount(0)); }
 
+    let ships = ships.get(&data.ships).unwrap();
     let mut main_ship_bundle = ShipBundle::from(
         textures.ship.clone(),
+        ships,
         selected_ship.0.model(),
         vec2(HALF_WIDTH, 24.),
     );
@@ -106,9 +133,12 @@ fn enter(
     main_ship_bundle.ship.shot_speed *= ship_status.shot_speed_multiplier();
     main_ship_bundle.ship.shot_frequency *= ship_status.shot_frequency_multiplier();
 
+    // The hull gives the stats and the module the weapons, upgrades stack on top
+    let module = ships.module(selected_module.0);
+    main_ship_bundle.weapons = ShipWeapons::new(&main_ship_bundle.ship, module.weapons.clone());
     if ship_status.has_upgrade(Upgrades::SideShots) {
-        main_ship_bundle.weapons.weapons.push(Weapon::new(Shots::Bullet, &main_ship_bundle.ship, vec2(-6., 4.), Angle(180. - 45.)));
-        main_ship_bundle.weapons.weapons.push(Weapon::new(Shots::Bullet, &main_ship_bundle.ship, vec2(6., 4.), Angle(45.)));
+        main_ship_bundle.weapons.weapons.push(Weapon::new(Shots::Bullet, &main_ship_bundle.ship, vec2(-6., 4.), Targeting::Fixed(Angle(180. - 45.))));
+        main_ship_bundle.weapons.weapons.push(Weapon::new(Shots::Bullet, &main_ship_bundle.ship, vec2(6., 4.), Targeting::Fixed(Angle(45.))));
     }
 
     commands
@@ -128,6 +158,22 @@ fn enter(
         .insert(SpaceUI)
     ;
 
+    // Empty until fire is held
+    commands
+        .spawn(SpriteBundle {
+            sprite: Sprite {
+                anchor: Anchor::BottomLeft,
+                color: Palette::Greyscale.colors()[2],
+                ..default()
+            },
+            texture: textures.bar.clone(),
+            ..default()
+        })
+        .insert(ChargeBar)
+        .insert(FakeTransform::from_xyz_and_scale(8., 1., z_pos::GUI, 0., 1.))
+        .insert(SpaceUI)
+    ;
+
     let state = *state.get();
     if state == GameState::Elite || state == GameState::Boss {
         commands
@@ -138,6 +184,7 @@ fn enter(
                 ..default()
             })
             .insert(SpaceUI)
+            .insert(EliteLabel)
         ;
 
         commands
@@ -158,12 +205,15 @@ fn enter(
         ;
     }
 
-    commands
-        .spawn(main_ship_bundle)
+    let mut main_ship = commands.spawn(main_ship_bundle);
+    main_ship
         .insert(MainShip)
         .insert(ShotUpgrades(ship_status.shot_upgrades()))
         .insert(SpaceUI)
     ;
+    if let Some(charged) = module.charged {
+        main_ship.insert(Charge::new(charged, ship_status.damage_multiplier(), ship_status.shot_frequency_multiplier()));
+    }
 
     // GUI
     commands
@@ -189,7 +239,9 @@ fn enter(
 
     commands
         .spawn(Text2dBundle {
-            text: Text::from_section("Pause", TextStyles::Basic.style(&fonts)),
+            text: Text::from_section("Pause\n\nSelect: map", TextStyles::Basic.style(&fonts))
+                .with_alignment(TextAlignment::Center)
+            ,
             transform: Transform::from_xyz(HALF_WIDTH, HALF_HEIGHT, z_pos::PAUSE),
             visibility: Visibility::Hidden,
             ..default()
@@ -211,7 +263,7 @@ fn enter(
 
     commands
         .spawn(Text2dBundle {
-            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * Route::act_len())), TextStyles::Basic.style(&fonts)),
+            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
             text_anchor: Anchor::BottomCenter,
             transform: Transform::from_xyz(HALF_WIDTH, 2., z_pos::GUI),
             ..default()
@@ -231,16 +283,25 @@ fn update_gui(
     }
 }
 
+fn show_affixes(
+    elites: Query<&Affixes, Added<Affixes>>,
+    mut label: Query<&mut Text, With<EliteLabel>>,
+) {
+    let (Ok(affixes), Ok(mut label)) = (elites.get_single(), label.get_single_mut()) else { return; };
+    label.sections[0].value = format!("Elite: {}", affixes.text());
+}
+
 fn update_life(
     main_ship: Query<&Ship, With<MainShip>>,
     mut bar_transform: Query<&mut FakeTransform, With<LifeBar>>,
     mut damaged: EventReader<DamageEvent>,
-    enemies: Query<&Ship, Without<MainShip>>,
+    enemies: Query<(&Ship, Option<&BossParts>), Without<MainShip>>,
+    regenerated: Query<(), (With<Affixes>, Changed<Ship>)>,
     mut elite_bar_transform: Query<&mut FakeTransform, (With<EliteLifeBar>, Without<LifeBar>)>,
     ship_status: Res<ShipStatus>,
     state: Res<State<GameState>>,
 ) {
-    if damaged.is_empty() && !ship_status.is_changed() { return; }
+    if damaged.is_empty() && !ship_status.is_changed() && regenerated.is_empty() { return; }
     damaged.clear();
 
     if let Ok(ship) = main_ship.get_single() {
@@ -252,21 +313,58 @@ fn update_life(
 
     if *state.get() == GameState::Space { return; }
 
-    for enemy in enemies.iter() {
+    // Bosses count the health of their parts
+    for (enemy, parts) in enemies.iter() {
         if !enemy.model.is_elite() { continue; }
         elite_bar_transform.single_mut().scale = Some(vec2(
-            enemy.health / enemy.max_health * (WIDTH as f32 - 16.),
+            boss::health_ratio(enemy, parts) * (WIDTH as f32 - 16.),
             1.,
         ));
     }
 }
 
+fn update_charge(
+    main_ship: Query<&Charge, (With<MainShip>, Changed<Charge>)>,
+    mut bar: Query<&mut FakeTransform, With<ChargeBar>>,
+) {
+    let (Ok(charge), Ok(mut bar)) = (main_ship.get_single(), bar.get_single_mut()) else { return; };
+    bar.scale = Some(vec2((charge.level * HEALTH_BAR_SIZE as f32).round(), 1.));
+}
+
+/// Marks the health thresholds of the phases of the boss on its life bar
+fn add_phase_markers(
+    mut commands: Commands,
+    textures: Res<Textures>,
+    bosses: Query<&BossPhases, Added<BossPhases>>,
+) {
+    for phases in bosses.iter() {
+        for threshold in phases.thresholds() {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        anchor: Anchor::BottomLeft,
+                        ..default()
+                    },
+                    texture: textures.bar.clone(),
+                    ..default()
+                })
+                .insert(FakeTransform::from_xyz_and_scale(
+                    8. + (threshold * (WIDTH as f32 - 16.)).round(), HEIGHT as f32 - 17., z_pos::GUI,
+                    1., 2.,
+                ))
+                .insert(SpaceUI)
+            ;
+        }
+    }
+}
+
 #[derive(Component)]
 struct Shield(f32);
 
 fn update_shield(
     mut commands: Commands,
     keys: Res<Input<KeyCode>>,
+    controls: Res<Controls>,
     mut shield: Query<(Entity, &mut FakeTransform, &mut Shield), Without<MainShip>>,
     player: Query<&FakeTransform, With<MainShip>>,
     mut ship_status: ResMut<ShipStatus>,
@@ -285,7 +383,7 @@ fn update_shield(
         }
         shield.0 -= time.delta_seconds();
         if shield.0 <= 0. { commands.entity(e).despawn_recursive(); }
-    } else if keys.just_pressed(KeyCode::Down) {
+    } else if keys.just_pressed(controls.shield_key()) {
         if ship_status.remove(&Items::Shield) {
             sfx.send(PlaySFXEvent(SFX::Shield));
             // Spawn new shield
@@ -294,7 +392,7 @@ fn update_shield(
                     texture: textures.shield.clone(),
                     ..default()
                 })
-                .insert(FakeTransform::from_xyz(player_pos.translation.x, player_pos.translation.y + space::SHIELD_OFFSET, player_pos.translation.y))
+                .insert(FakeTransform::from_xyz(player_pos.translation.x, player_pos.translation.y + space::SHIELD_OFFSET, z_pos::SHIELD))
                 .insert(Hitbox(vec2(16., 2.)))
                 .insert(Shield(space::SHIELD_DURATION * if ship_status.has_upgrade(Upgrades::BetterShields) { 2. } else { 1. }))
                 .insert(Ship::shield())
@@ -304,24 +402,19 @@ fn update_shield(
     }
 }
 
-#[derive(Component)]
-struct Missile;
-
 fn update_missiles(
     mut commands: Commands,
     keys: Res<Input<KeyCode>>,
-    player: Query<(&FakeTransform, &Ship), (With<MainShip>, Without<Missile>)>,
+    controls: Res<Controls>,
+    player: Query<(&FakeTransform, &Ship), With<MainShip>>,
     mut ship_status: ResMut<ShipStatus>,
-    mut missiles: Query<&mut FakeTransform, With<Missile>>,
-    mut enemies: Query<(&FakeTransform, &Ship), (Without<MainShip>, Without<Missile>)>,
-    time: Res<Time>,
     textures: Res<Textures>,
     mut sfx: EventWriter<PlaySFXEvent>,
 ) {
     let Ok((ship_pos, ship)) = player.get_single() else { return; };
 
     // Spawn missiles
-    if keys.just_pressed(KeyCode::Up) && ship_status.remove(&Items::Missile) {
+    if keys.just_pressed(controls.missile_key()) && ship_status.remove(&Items::Missile) {
         sfx.send(PlaySFXEvent(SFX::Missile));
         let offsets = if ship_status.has_upgrade(Upgrades::BetterMissiles) {
             vec![vec2(-4., 4.), vec2(4., 4.)]
@@ -329,7 +422,7 @@ fn update_missiles(
             vec![vec2(0., 4.)]
         };
         for offset in offsets {
-            let weapon = Weapon::new(Shots::Missile, &ship, offset, Angle(90.));
+            let weapon = Weapon::new(Shots::Missile, &ship, offset, Targeting::Fixed(Angle(90.)));
             commands
                 .spawn(SpriteSheetBundle {
                     sprite: TextureAtlasSprite {
@@ -348,28 +441,18 @@ fn update_missiles(
                     ship_pos.translation.y + weapon.offset.y,
                     z_pos::SHOTS,
                 ))
-                .insert(Missile)
+                .insert(HomingShot::new(Homing { turn_rate: space::MISSILE_TURN_RATE, range: space::MISSILE_RANGE, lock_on: 0 }))
             ;
         }
     }
-
-    // Auto aim
-    for mut pos in missiles.iter_mut() {
-        if let Some(closest) = enemies
-            .iter()
-            .filter(|(p, s)| !s.friendly && (p.translation.xy().distance(pos.translation.xy()) as usize) < space::MISSILE_RANGE)
-            .map(|(p, _)| p.translation.xy())
-            .min_by_key(|p| p.distance(pos.translation.xy()) as usize) {
-            pos.translation.x += time.delta_seconds() * space::MISSILE_SPEED * if closest.x > pos.translation.x { 1. } else { -1. };
-        }
-    }
 }
 
-#[derive(Eq, PartialEq)]
-enum Position { Left, Center, Right }
-
+/// Option lane, number of lanes and node in the next column of the route
 #[derive(Component)]
-struct NextLevelOption(Position, Level);
+struct NextLevelOption(usize, usize, usize);
+
+/// Center of the [lane]-th of [lanes] lanes splitting the screen
+fn lane_x(lane: usize, lanes: usize) -> f32 { WIDTH as f32 / lanes as f32 * (lane as f32 + 0.5) }
 
 #[derive(Component)]
 struct NextLevelSelectionSprite;
@@ -388,12 +471,13 @@ fn update_next(
     mut transition: ResMut<ScreenTransition>,
     fonts: Res<Fonts>,
     mut sfx: EventWriter<PlaySFXEvent>,
+    mut rng: ResMut<RunRng>,
 ) {
     let Ok(mut bars_pos) = bars.get_single_mut() else { return; };
     let Ok((e, mut ship_pos, rush)) = ship.get_single_mut() else { return; };
 
     let mut do_transition = false;
-    let mut next_state: Option<Level> = None;
+    let mut next_node: Option<usize> = None;
 
     let bars_y = bars_pos.translation.y;
     let dy = space::NEXT_LEVEL_SPEED_Y * time.delta_seconds() * if rush.is_some() { 0. } else { 1. };
@@ -402,7 +486,6 @@ fn update_next(
         let ship_y = ship_pos.translation.y;
         if ship_y > HEIGHT as f32 + 64. && transition.is_none() {
             // Transition to next state
-            route.advance();
             do_transition = true;
         }
         ship_pos.translation.y += space::RUSH_SPEED_Y * time.delta_seconds();
@@ -417,36 +500,22 @@ fn update_next(
     for (option, mut pos, mut text) in next.iter_mut() {
         pos.translation.y += dy;
 
-        if option.0 == Position::Left {
-            if ship_pos.translation.x <= HALF_WIDTH {
-                text.sections[0].style = TextStyles::Basic.style(&fonts);
-                bars_pos.translation.x = WIDTH as f32 / 4.;
-                next_state = Some(option.1);
-            } else {
-                text.sections[0].style = TextStyles::Gray.style(&fonts);
-            }
-        }
-
-        if option.0 == Position::Right {
-            if ship_pos.translation.x > HALF_WIDTH {
-                text.sections[0].style = TextStyles::Basic.style(&fonts);
-                bars_pos.translation.x = WIDTH as f32 / 4. * 3.;
-                next_state = Some(option.1);
-            } else {
-                text.sections[0].style = TextStyles::Gray.style(&fonts);
-            }
-        }
-
-        if option.0 == Position::Center {
-            next_state = Some(option.1);
+        let NextLevelOption(lane, lanes, node) = *option;
+        let ship_lane = ((ship_pos.translation.x / WIDTH as f32 * lanes as f32) as usize).min(lanes - 1);
+        if lane == ship_lane {
+            text.sections[0].style = TextStyles::Basic.style(&fonts);
+            bars_pos.translation.x = lane_x(lane, lanes);
+            next_node = Some(node);
+        } else {
+            text.sections[0].style = TextStyles::Gray.style(&fonts);
         }
     }
 
-    if do_transition && next_state.is_some() {
-        let mut state = next_state.unwrap().state();
+    if let (true, Some(node)) = (do_transition, next_node) {
+        route.choose(node, &mut rng);
+        let mut state = route.chosen;
         if state == GameState::Shop && route.are_shopkeepers_angry() { state = GameState::Elite; }
         if state == GameState::Space { state = GameState::Dummy; }
-        route.chosen = state;
         transition.set_if_neq(ScreenTransition::to(state));
     }
 }
@@ -473,6 +542,11 @@ fn on_cleared(
     if cleared.is_empty() { return; }
     cleared.clear();
 
+    if route.mode == GameMode::Practice {
+        transition.set_if_neq(ScreenTransition::to(GameState::Practice));
+        return;
+    }
+
     match state.get() {
         GameState::Elite => {
             text.0 = "Elite defeated!".to_string();
@@ -499,23 +573,20 @@ fn on_cleared(
     }
 
     // Spawn next level options
-    for (name, x, position, level) in match route.route.0[route.level + 1] {
-        RouteElement::Level(l) => vec![
-            (l.name().to_owned(), HALF_WIDTH, Position::Center, l),
-        ],
-        RouteElement::Choice(l1, l2) => vec![
-            (l1.name().to_owned(), WIDTH as f32 / 4., Position::Left, l1),
-            (l2.name().to_owned(), WIDTH as f32 / 4. * 3., Position::Right, l2),
-        ]
-    } {
+    let (column, nodes) = route.next();
+    // Skipped columns may merge more than two nodes, their names are shortened to fit
+    let lanes = nodes.len();
+    for (lane, &node) in nodes.iter().enumerate() {
+        let level = route.route.0[column][node].level;
+        let name = if lanes > 2 { level.short_name() } else { level.name() };
         commands
             .spawn(Text2dBundle {
                 text: Text::from_section(name, TextStyles::Basic.style(&fonts)),
                 text_anchor: Anchor::Center,
                 ..default()
             })
-            .insert(FakeTransform::from_xyz(x, HEIGHT as f32 + 16., z_pos::GUI))
-            .insert(NextLevelOption(position, level))
+            .insert(FakeTransform::from_xyz(lane_x(lane, lanes), HEIGHT as f32 + 16., z_pos::GUI))
+            .insert(NextLevelOption(lane, lanes, node))
             .insert(SpaceUI)
         ;
     }
@@ -550,7 +621,7 @@ fn pause(
 
 fn exit(
     mut commands: Commands,
-    to_clean: Query<Entity, Or<(With<SpaceUI>, With<Ship>, With<Shot>)>>,
+    to_clean: Query<Entity, Or<(With<SpaceUI>, With<Ship>, With<Shot>, With<Formation>)>>,
 ) {
     for id in to_clean.iter() {
         commands
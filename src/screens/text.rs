@@ -1,12 +1,13 @@
 use bevy::app::App;
 use bevy::prelude::*;
 use bevy::sprite::Anchor;
-use rand::{RngCore, thread_rng};
+use rand::RngCore;
 
 use crate::GameState;
 use crate::graphics::{ScreenTransition, TextStyles};
 use crate::logic::{Items, ShipStatus};
-use crate::logic::route::{CurrentRoute, Route};
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
 use crate::screens::Fonts;
 use crate::util::{HALF_HEIGHT, HALF_WIDTH, z_pos};
 
@@ -34,8 +35,10 @@ fn on_enter_repair(
     mut text: ResMut<SimpleText>,
     mut ship_status: ResMut<ShipStatus>,
     mut next_state: ResMut<NextState<GameState>>,
+    route: Res<CurrentRoute>,
+    mut rng: ResMut<RunRng>,
 ) {
-    let justine = match thread_rng().next_u32() % 10 {
+    let justine = match rng.at_level(route.level).next_u32() % 10 {
         0 => {
             ship_status.add(&Items::Missile);
             "\n1 missile found!"
@@ -60,11 +63,12 @@ fn update(
     mut route: ResMut<CurrentRoute>,
     mut ship_status: ResMut<ShipStatus>,
     mut transition: ResMut<ScreenTransition>,
+    mut rng: ResMut<RunRng>,
 ) {
     wait.0 -= time.delta_seconds();
     if wait.0 < 0. && transition.is_none() {
         let act = route.act();
-        route.advance();
+        route.advance(&mut rng);
         if route.act() != act && !route.win() {
             // Heal between acts
             let max_health = ship_status.health().1;
@@ -96,7 +100,7 @@ fn enter(
 
     commands
         .spawn(Text2dBundle {
-            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * Route::act_len())), TextStyles::Basic.style(&fonts)),
+            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
             text_anchor: Anchor::BottomCenter,
             transform: Transform::from_xyz(HALF_WIDTH, 2., z_pos::GUI),
             ..default()
@@ -6,10 +6,12 @@ use bevy::sprite::Anchor;
 use crate::GameState;
 use crate::graphics::{ScreenTransition, StarsSpeed, TextStyles};
 use crate::logic::{Items, ShipStatus};
-use crate::logic::route::{CurrentRoute, Route};
+use crate::logic::rng::RunRng;
+use crate::logic::route::CurrentRoute;
 use crate::logic::upgrades::Upgrades;
 use crate::music::{PlaySFXEvent, SFX};
 use crate::screens::{Fonts, Textures};
+use crate::screens::map::map_closed;
 use crate::screens::shop::Select;
 use crate::util::{HALF_WIDTH, z_pos};
 
@@ -21,7 +23,7 @@ struct UpgradeUI;
 impl Plugin for UpgradePlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Update, update.run_if(in_state(GameState::Upgrade)))
+            .add_systems(Update, update.run_if(in_state(GameState::Upgrade).and_then(map_closed)))
             .add_systems(OnEnter(GameState::Upgrade), enter)
             .add_systems(OnExit(GameState::Upgrade), exit)
         ;
@@ -36,6 +38,7 @@ fn update(
     mut transition: ResMut<ScreenTransition>,
     mut dot: Query<&mut Transform, With<SelectionDot>>,
     mut sfx: EventWriter<PlaySFXEvent>,
+    mut rng: ResMut<RunRng>,
 ) {
     // Select previous / next option
     if keys.just_pressed(KeyCode::Up) {
@@ -53,7 +56,7 @@ fn update(
     if transition.is_none() && keys.just_pressed(KeyCode::Space) {
         sfx.send(PlaySFXEvent(SFX::Buy));
         status.add(&Items::Upgrade(upgrade));
-        route.advance();
+        route.advance(&mut rng);
         transition.set_if_neq(ScreenTransition::to(GameState::Dummy));
     }
 }
@@ -68,8 +71,9 @@ fn enter(
     status: Res<ShipStatus>,
     mut star_field: ResMut<StarsSpeed>,
     route: Res<CurrentRoute>,
+    mut rng: ResMut<RunRng>,
 ) {
-    star_field.set_by_level(route.level);
+    star_field.set_by_level(route.loop_level());
 
     commands
         .spawn(SpriteBundle {
@@ -84,9 +88,10 @@ fn enter(
         .insert(UpgradeUI)
     ;
 
+    let rng = rng.at_level(route.level);
     let upgrades = vec![
-        (vec2(28., 101.), Upgrades::new_non_stat_upgrade(&status)),
-        (vec2(28., 65.), Upgrades::random_stat_upgrade()),
+        (vec2(28., 101.), Upgrades::new_non_stat_upgrade(&status, rng)),
+        (vec2(28., 65.), Upgrades::random_stat_upgrade(rng)),
     ];
 
     for (pos, u) in upgrades.iter() {
@@ -132,7 +137,7 @@ fn enter(
 
     commands
         .spawn(Text2dBundle {
-            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * Route::act_len())), TextStyles::Basic.style(&fonts)),
+            text: Text::from_section(format!("{}-{}", route.act(), (route.level + 1 - (route.act() - 1) * route.act_len())), TextStyles::Basic.style(&fonts)),
             text_anchor: Anchor::BottomCenter,
             transform: Transform::from_xyz(HALF_WIDTH, 2., z_pos::GUI),
             ..default()
@@ -2,6 +2,7 @@ use std::f32::consts::PI;
 
 use bevy::math::{Vec2, vec2};
 use bevy::prelude::{Res, State, States};
+use serde::Deserialize;
 
 use crate::entities::Shots;
 use crate::logic::{Items, ShipStatus};
@@ -17,8 +18,7 @@ pub const SCALE: f32 = 4.;
 pub const BORDER: f32 = 2.;
 
 pub mod space {
-    use crate::entities::Ships;
-    use crate::util::{base_stats, HALF_HEIGHT, WIDTH};
+    use crate::util::{base_stats, HALF_HEIGHT, HEIGHT, WIDTH};
 
     pub const BLINK_INTERVAL: f32 = 10. / 60.;
     pub const BLINK_DURATION: f32 = BLINK_INTERVAL * 8.;
@@ -27,35 +27,42 @@ pub mod space {
 
     pub const TIME_RATIO_DEAD: f32 = 0.6;
 
+    /// Bosses are invulnerable and don't shoot while switching phases
+    pub const PHASE_TRANSITION: f32 = BLINK_INTERVAL * 9.;
+
     pub const SHIELD_OFFSET: f32 = 8.;
+
+    /// Vertical bounds of the main ship with the free controls, clear of the HUD rows
+    pub const PLAY_AREA_BOTTOM: f32 = 18.;
+    pub const PLAY_AREA_TOP: f32 = HEIGHT as f32 - 20.;
     pub const SHIELD_DURATION: f32 = 6.;
-    pub const MISSILE_RANGE: usize = WIDTH / 3;
-    pub const MISSILE_SPEED: f32 = base_stats::SPEED / 3.;
+    pub const MISSILE_RANGE: f32 = WIDTH as f32 / 3.;
+    /// In degrees per second
+    pub const MISSILE_TURN_RATE: f32 = 180.;
+
+    /// Seconds a beam deals damage, once per tick
+    pub const BEAM_DURATION: f32 = 0.75;
+    pub const BEAM_TICK: f32 = 0.125;
+    /// Long enough to cross the screen from anywhere
+    pub const BEAM_LENGTH: f32 = (WIDTH + HEIGHT) as f32;
 
     pub const NEXT_LEVEL_SPEED_Y: f32 = -18.;
     pub const NEXT_LEVEL_CHOICE_Y: f32 = HALF_HEIGHT;
     pub const RUSH_SPEED_Y: f32 = base_stats::SPEED * 12.;
 
-    pub fn time_ratio(level: usize) -> f32 { 1. + level as f32 / 26. * 0.3 }
+    pub fn time_ratio(level: usize, loops: usize) -> f32 { 1. + level as f32 / 26. * 0.3 + loops as f32 * 0.1 }
 
-    pub fn patterns_nb(level: usize) -> usize {
-        match level {
-            0..=8 => 2,
-            9..=17 => 3,
-            _ => 4,
-        }
-    }
+    /// Enemy health and shot frequency multipliers after [loops] endless loops
+    pub fn loop_health_ratio(loops: usize) -> f32 { 1. + loops as f32 * 0.5 }
+    pub fn loop_shot_frequency_ratio(loops: usize) -> f32 { 1. + loops as f32 * 0.2 }
+}
 
-    pub fn credits(model: Ships) -> i16 {
-        match model {
-            Ships::Player(_) => 0,
-            Ships::Invader(n) if n <= 3 => 2,
-            Ships::Invader(n) if n <= 6 => 5,
-            Ships::Invader(_) => 10,
-            Ships::Elite(_) => 25,
-            Ships::Boss(_) => 50,
-        }
-    }
+pub mod charge {
+    /// Seconds to fully charge a shot without upgrades
+    pub const CHARGE_TIME: f32 = 1.5;
+    /// Releasing fire earlier doesn't shoot
+    pub const MIN_CHARGE: f32 = 0.2;
+    pub const PIERCING_CHARGE: f32 = 1.;
 }
 
 pub mod star_field {
@@ -73,8 +80,17 @@ pub mod hud {
     pub const HEALTH_BAR_SIZE: usize = 24;
 }
 
+pub mod map {
+    pub const FIRST_ROW_Y: f32 = 10.;
+    pub const ROW_HEIGHT: f32 = 14.;
+    /// Dots drawn between two rows to show a path
+    pub const EDGE_DOTS: usize = 3;
+}
+
 pub mod z_pos {
     pub const STAR_FIELD: f32 = 10.;
+    /// Below the ships
+    pub const SHIELD: f32 = 24.;
     pub const SHIPS: f32 = 30.;
     pub const SHOTS: f32 = 31.;
     pub const PAUSE: f32 = 39.;
@@ -83,6 +99,8 @@ pub mod z_pos {
     pub const HANGAR_TEXT: f32 = 51.;
     pub const SHOP: f32 = 50.;
     pub const SHOP_TEXT: f32 = 51.;
+    pub const MAP: f32 = 60.;
+    pub const MAP_TEXT: f32 = 61.;
 }
 
 pub mod base_stats {
@@ -115,6 +133,8 @@ impl Shots {
             Shots::Energy => 6.0,
             Shots::DualBeam => 1.25,
             Shots::Missile => 10.0,
+            // Per tick of the beam
+            Shots::Beam => 1.0,
         }
     }
 
@@ -125,6 +145,7 @@ impl Shots {
             Shots::Missile => 1.0,
             Shots::Energy => 1.75,
             Shots::DualBeam => 0.9,
+            Shots::Beam => 2.0,
         }
     }
 }
@@ -145,10 +166,84 @@ pub mod items {
             (GameMode::Act3, vec![(Items::Missile, 4), (Items::Shield, 4),]),
             (GameMode::LastBoss, vec![(Items::Missile, 1), (Items::Shield, 2),]),
             (GameMode::BossRush, vec![(Items::Missile, 3), (Items::Shield, 3),]),
+            (GameMode::Endless, vec![(Items::Missile, 2), (Items::Shield, 2),]),
+            (GameMode::Daily, vec![(Items::Missile, 1), (Items::Shield, 1),]),
+            (GameMode::Practice, vec![]),
         ]);
     }
 }
 
+pub mod director {
+    /// Bounds of the intensity, 1 being the regular difficulty
+    pub const MIN_INTENSITY: f32 = 0.75;
+    pub const MAX_INTENSITY: f32 = 1.3;
+    /// Intensity from which enemies are drawn from the pool of the next / previous act
+    pub const STRONGER_ENEMIES: f32 = 1.15;
+    pub const WEAKER_ENEMIES: f32 = 0.85;
+
+    /// Seconds for recent damage and kills to fade
+    pub const MEMORY: f32 = 10.;
+    /// Seconds for the intensity to get most of the way to its target
+    pub const REACTION_TIME: f32 = 8.;
+
+    /// Kills per second of a player keeping up with the waves
+    pub const KILL_RATE: f32 = 0.15;
+    /// Credits from which the player is considered rich
+    pub const RICH: i16 = 150;
+
+    pub const HULL_WEIGHT: f32 = 0.4;
+    pub const DAMAGE_WEIGHT: f32 = 0.05;
+    pub const KILL_WEIGHT: f32 = 1.;
+    pub const CREDITS_WEIGHT: f32 = 0.1;
+}
+
+pub mod affixes {
+    use crate::util::base_stats;
+
+    /// Elites roll 1 affix in the first act, 2 afterwards
+    pub const MAX_AFFIXES: usize = 2;
+    /// Strength gained by the affixes with each act
+    pub const ACT_SCALING: f32 = 0.25;
+    pub const CREDITS: i16 = 15;
+
+    pub const BARRIER_HEALTH: f32 = base_stats::HEALTH;
+    pub const BARRIER_COOLDOWN: f32 = 6.;
+    /// Ratio of the max health regenerated per second
+    pub const REGENERATION: f32 = 0.015;
+    /// Extra fire rate of an enraged elite at 0 health
+    pub const ENRAGED_FIRE_RATE: f32 = 1.;
+    pub const TELEPORT_INTERVAL: f32 = 6.;
+    /// Max horizontal distance between a teleporting elite and its path
+    pub const TELEPORT_RANGE: f32 = 40.;
+}
+
+pub mod casino {
+    use crate::logic::event::Effect;
+
+    pub const SLOTS_BET: i16 = 10;
+    pub const CRATE_BET: i16 = 20;
+    pub const CRATES: usize = 3;
+
+    /// Reel symbols, their weight and the prize for 3 of them
+    pub const SLOTS: [(&str, u32, Effect); 4] = [
+        ("7", 1, Effect::Upgrade),
+        ("$", 3, Effect::Credits(60)),
+        ("M", 4, Effect::Missiles(2)),
+        ("X", 4, Effect::Hull(-2)),
+    ];
+    /// Prize for 2 identical symbols
+    pub const SLOTS_PAIR: Effect = Effect::Credits(15);
+
+    /// Weighted content of the hidden crates
+    pub const CRATE_CONTENTS: [(u32, Effect); 5] = [
+        (2, Effect::Upgrade),
+        (3, Effect::Credits(40)),
+        (3, Effect::Missiles(2)),
+        (3, Effect::Shields(2)),
+        (4, Effect::Hull(-3)),
+    ];
+}
+
 pub mod shop {
     use crate::logic::Items;
 
@@ -164,7 +259,8 @@ pub mod shop {
 }
 
 /// Angle in degrees
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(transparent)]
 pub struct Angle(pub f32);
 impl Angle {
     pub fn to_rad(&self) -> f32 { self.0 * PI / 180. }
@@ -186,4 +282,15 @@ pub fn in_states<S: States>(states: Vec<S>) -> impl FnMut(Res<State<S>>) -> bool
 
 pub fn format_credits(credits: i16) -> String { format!("Credits: {:03}", credits) }
 
-pub fn format_items(status: &ShipStatus) -> String { format!("M{} S{}", status.get(&Items::Missile), status.get(&Items::Shield)) }
\ No newline at end of file
+pub fn format_items(status: &ShipStatus) -> String { format!("M{} S{}", status.get(&Items::Missile), status.get(&Items::Shield)) }
+
+/// Check that [file] loads, and that each (from, to, error) replacement makes it fail with [error]
+#[cfg(test)]
+pub fn assert_rejected<T: serde::de::DeserializeOwned>(file: &str, replacements: &[(&str, &str, &str)]) {
+    assert!(ron::from_str::<T>(file).is_ok());
+    for &(from, to, error) in replacements {
+        assert!(file.contains(from), "{} isn't in the file", from);
+        let result = ron::from_str::<T>(&file.replace(from, to));
+        assert!(result.is_err_and(|e| e.to_string().contains(error)), "{} -> {} should fail with \"{}\"", from, to, error);
+    }
+}
\ No newline at end of file